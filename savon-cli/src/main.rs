@@ -0,0 +1,411 @@
+//! `savon` command line tool.
+//!
+//! ```text
+//! savon generate <WSDL> [-o <FILE>] [options]   generate the Rust code
+//! savon inspect <WSDL>                          print the services, ports,
+//!                                               operations and types
+//! savon validate <WSDL>                         report unsupported constructs
+//! ```
+
+use savon_gen::gen::Generator;
+use savon_gen::wsdl::{self, SimpleType, Type, Wsdl};
+use std::path::{Path, PathBuf};
+use std::process;
+
+const USAGE: &str = "usage:
+    savon generate <WSDL> [-o <FILE>] [options]
+    savon inspect <WSDL>
+    savon validate <WSDL>
+
+generate options:
+    -o, --output <FILE>      write to FILE instead of the standard output
+    --module <NAME>          wrap the code in a module
+    --blocking               also generate a blocking client
+    --server                 also generate a server trait
+    --serde                  derive Serialize and Deserialize
+    --strict-types           reject values with a mismatching xsi:type
+    --derive <DERIVE>        add a derive to the generated types
+    --visibility <VIS>       visibility of the generated items
+    --type <XSD>=<RUST>      map an XSD type to a Rust type
+    --operation <NAME>       only generate this operation";
+
+#[derive(Debug)]
+enum Command {
+    Generate {
+        wsdl: PathBuf,
+        output: Option<PathBuf>,
+        generator: Box<Generator>,
+    },
+    Inspect {
+        wsdl: PathBuf,
+    },
+    Validate {
+        wsdl: PathBuf,
+    },
+    Help,
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let command = match args.next() {
+        Some(command) => command,
+        None => return Ok(Command::Help),
+    };
+    match command.as_str() {
+        "-h" | "--help" | "help" => return Ok(Command::Help),
+        "generate" | "inspect" | "validate" => {}
+        c => return Err(format!("unknown command {}", c)),
+    }
+
+    let mut wsdl = None;
+    let mut output = None;
+    let mut generator = Generator::new().with_rerun_if_changed(false);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {}", arg));
+        if command != "generate" && arg.starts_with('-') {
+            return Err(format!("unknown option {}", arg));
+        }
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(PathBuf::from(value()?)),
+            "--module" => generator = generator.with_module(value()?),
+            "--blocking" => generator = generator.with_blocking(true),
+            "--server" => generator = generator.with_server(true),
+            "--serde" => generator = generator.with_serde(true),
+            "--strict-types" => generator = generator.with_strict_types(true),
+            "--derive" => generator = generator.with_derive(value()?),
+            "--visibility" => generator = generator.with_visibility(value()?),
+            "--operation" => generator = generator.with_operation(value()?),
+            "--type" => {
+                let mapping = value()?;
+                let (xsd_type, rust_type) = mapping
+                    .split_once('=')
+                    .ok_or(format!("expected <XSD>=<RUST>, got {}", mapping))?;
+                generator = generator.with_type(xsd_type, rust_type);
+            }
+            a if a.starts_with('-') => return Err(format!("unknown option {}", a)),
+            _ if wsdl.is_some() => return Err(format!("unexpected argument {}", arg)),
+            _ => wsdl = Some(PathBuf::from(arg)),
+        }
+    }
+    let wsdl = wsdl.ok_or("missing WSDL path")?;
+
+    match command.as_str() {
+        "generate" => Ok(Command::Generate {
+            wsdl,
+            output,
+            generator: Box::new(generator),
+        }),
+        "inspect" => Ok(Command::Inspect { wsdl }),
+        _ => Ok(Command::Validate { wsdl }),
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))
+}
+
+fn parse(path: &Path) -> Result<Wsdl, String> {
+    wsdl::parse(&read(path)?).map_err(|e| format!("invalid WSDL {}: {}", path.display(), e))
+}
+
+/// Line of the `inspect` tree.
+struct Node {
+    label: String,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn new<S: Into<String>>(label: S) -> Self {
+        Node {
+            label: label.into(),
+            children: Vec::new(),
+        }
+    }
+
+    fn render(&self, prefix: &str, out: &mut String) {
+        for (i, child) in self.children.iter().enumerate() {
+            let last = i + 1 == self.children.len();
+            out.push_str(prefix);
+            out.push_str(if last { "└── " } else { "├── " });
+            out.push_str(&child.label);
+            out.push('\n');
+            child.render(
+                &format!("{}{}", prefix, if last { "    " } else { "│   " }),
+                out,
+            );
+        }
+    }
+}
+
+/// Fields of a type, expanding the complex types not already in `path`.
+fn fields(wsdl: &Wsdl, name: &str, path: &mut Vec<String>) -> Vec<Node> {
+    let complex = match wsdl.types.get(name) {
+        Some(Type::Complex(complex)) if !path.iter().any(|p| p == name) => complex,
+        _ => return Vec::new(),
+    };
+
+    path.push(name.to_string());
+    let nodes = complex
+        .fields
+        .iter()
+        .map(|(field_name, (attributes, field_type))| {
+            // same precedence as the generated fields: `Vec`, then `Option`
+            let occurs = if attributes.is_repeated() {
+                "[]"
+            } else if attributes.is_optional() {
+                "?"
+            } else if attributes.nillable {
+                " (nillable)"
+            } else {
+                ""
+            };
+            let mut node = Node::new(format!(
+                "{}: {}{}",
+                field_name,
+                field_type.xsd_name(),
+                occurs
+            ));
+            if let SimpleType::Complex(t) = field_type {
+                node.children = fields(wsdl, t, path);
+            }
+            node
+        })
+        .collect();
+    path.pop();
+
+    nodes
+}
+
+fn message(wsdl: &Wsdl, kind: &str, name: &str) -> Node {
+    match wsdl.messages.get(name) {
+        Some(message) => Node {
+            label: format!("{} {}: {}", kind, name, message.part_element),
+            children: fields(wsdl, &message.part_element, &mut Vec::new()),
+        },
+        None => Node::new(format!("{} {}", kind, name)),
+    }
+}
+
+fn inspect(wsdl: &Wsdl) -> String {
+    let mut service = Node::new(format!("service {} ({})", wsdl.name, wsdl.target_namespace));
+    for port in wsdl.ports.iter() {
+        service.children.push(Node::new(format!(
+            "port {}: {}{}",
+            port.name,
+            port.binding,
+            port.address
+                .as_ref()
+                .map(|a| format!(" at {}", a))
+                .unwrap_or_default()
+        )));
+    }
+
+    let mut port_type = Node::new(format!("portType {}", wsdl.port_type));
+    for (name, operation) in wsdl.operations.iter() {
+        let mut node = Node::new(match operation.soap_action.as_ref() {
+            Some(action) => format!("operation {} (SOAPAction {})", name, action),
+            None => format!("operation {}", name),
+        });
+        node.children.extend(
+            operation
+                .input
+                .iter()
+                .map(|m| message(wsdl, "input", m))
+                .chain(operation.output.iter().map(|m| message(wsdl, "output", m)))
+                .chain(
+                    operation
+                        .faults
+                        .iter()
+                        .flatten()
+                        .map(|m| message(wsdl, "fault", m)),
+                ),
+        );
+        port_type.children.push(node);
+    }
+    service.children.push(port_type);
+
+    let mut out = format!("{}\n", service.label);
+    service.render("", &mut out);
+    out
+}
+
+/// Run the command, returns whether it succeeded.
+fn run(command: Command) -> Result<bool, String> {
+    match command {
+        Command::Help => println!("{}", USAGE),
+        Command::Generate {
+            wsdl,
+            output: Some(output),
+            generator,
+        } => {
+            let directory = output.parent().unwrap_or_else(|| Path::new(""));
+            let file_name = output.file_name().ok_or("invalid output path")?;
+            generator
+                .with_out_dir(directory)
+                .with_output(file_name.to_string_lossy())
+                .write(&wsdl)
+                .map_err(|e| format!("cannot generate {}: {}", wsdl.display(), e))?;
+        }
+        Command::Generate {
+            wsdl,
+            output: None,
+            generator,
+        } => {
+            let code = generator
+                .generate(&parse(&wsdl)?)
+                .map_err(|e| format!("cannot generate {}: {}", wsdl.display(), e))?;
+            print!("{}", code);
+        }
+        Command::Inspect { wsdl } => print!("{}", inspect(&parse(&wsdl)?)),
+        Command::Validate { wsdl } => {
+            let issues = wsdl::validate(&read(&wsdl)?)
+                .map_err(|e| format!("invalid WSDL {}: {}", wsdl.display(), e))?;
+            for issue in issues.iter() {
+                println!("{}: {}", issue.location, issue.message);
+            }
+            if issues.is_empty() {
+                println!("{}: no unsupported constructs", wsdl.display());
+            }
+            return Ok(issues.is_empty());
+        }
+    }
+
+    Ok(true)
+}
+
+fn main() {
+    let result = parse_args(std::env::args().skip(1)).and_then(run);
+
+    match result {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &str) -> Result<Command, String> {
+        parse_args(args.split_whitespace().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn arguments() {
+        match args("generate quotes.wsdl -o out/quotes.rs --blocking --type dateTime=String") {
+            Ok(Command::Generate {
+                wsdl,
+                output,
+                generator,
+            }) => {
+                assert_eq!(wsdl, PathBuf::from("quotes.wsdl"));
+                assert_eq!(output, Some(PathBuf::from("out/quotes.rs")));
+                let generator = format!("{:?}", generator);
+                assert!(generator.contains("blocking: true"));
+                assert!(generator.contains("\"dateTime\": \"String\""));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(
+            args("inspect quotes.wsdl"),
+            Ok(Command::Inspect { .. })
+        ));
+        assert!(matches!(args(""), Ok(Command::Help)));
+
+        assert_eq!(args("inspect").unwrap_err(), "missing WSDL path");
+        assert_eq!(
+            args("inspect quotes.wsdl --blocking").unwrap_err(),
+            "unknown option --blocking"
+        );
+        assert_eq!(
+            args("generate quotes.wsdl --type dateTime").unwrap_err(),
+            "expected <XSD>=<RUST>, got dateTime"
+        );
+        assert_eq!(
+            args("generate quotes.wsdl --module").unwrap_err(),
+            "missing value for --module"
+        );
+        assert_eq!(args("check").unwrap_err(), "unknown command check");
+    }
+
+    #[test]
+    fn generate_errors() {
+        let wsdl = std::env::temp_dir().join(format!("savon-cli-{}.wsdl", std::process::id()));
+        let example = std::fs::read_to_string("../assets/example.wsdl").unwrap();
+        std::fs::write(
+            &wsdl,
+            example.replace(
+                r#"<output message="tns:GetLastTradePriceOutput"/>"#,
+                r#"<output message="tns:GetLastTradePriceOutput"/><fault name="UnknownSymbol" message="tns:GetLastTradePriceOutput"/>"#,
+            ),
+        )
+        .unwrap();
+
+        let result = run(Command::Generate {
+            wsdl: wsdl.clone(),
+            output: None,
+            generator: Box::new(Generator::new()),
+        });
+        let valid = run(Command::Validate { wsdl: wsdl.clone() });
+        std::fs::remove_file(&wsdl).unwrap();
+        assert!(result
+            .unwrap_err()
+            .contains("declares faults, which are not supported"));
+        assert_eq!(valid, Ok(false));
+    }
+
+    #[test]
+    fn inspect_tree() {
+        let wsdl = parse(Path::new("../assets/example.wsdl")).unwrap();
+        assert_eq!(
+            inspect(&wsdl),
+            "service StockQuoteService (http://example.com/stockquote.wsdl)
+├── port StockQuotePort: StockQuoteSoapBinding at http://example.com/stockquote
+└── portType StockQuotePortType
+    └── operation GetLastTradePrice (SOAPAction http://example.com/GetLastTradePrice)
+        ├── input GetLastTradePriceInput: TradePriceRequest
+        │   └── tickerSymbol: string
+        └── output GetLastTradePriceOutput: TradePrice
+            └── price: float
+"
+        );
+    }
+
+    #[test]
+    fn inspect_occurrences() {
+        use savon_gen::wsdl::{Occurence, TypeAttribute};
+
+        let mut wsdl = parse(Path::new("../assets/example.wsdl")).unwrap();
+        let field = |name: &str, min_occurs, max_occurs, nillable| {
+            (
+                name.to_string(),
+                (
+                    TypeAttribute {
+                        nillable,
+                        min_occurs,
+                        max_occurs,
+                    },
+                    SimpleType::String,
+                ),
+            )
+        };
+        if let Some(Type::Complex(c)) = wsdl.types.get_mut("TradePrice") {
+            c.fields = vec![
+                field("note", Some(Occurence::Num(0)), None, false),
+                field("tag", None, Some(Occurence::Unbounded), true),
+                field("comment", None, None, true),
+            ];
+        }
+
+        assert!(inspect(&wsdl).ends_with(
+            "        └── output GetLastTradePriceOutput: TradePrice
+            ├── note: string?
+            ├── tag: string[]
+            └── comment: string (nillable)
+"
+        ));
+    }
+}
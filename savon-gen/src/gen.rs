@@ -0,0 +1,1300 @@
+//! Rust code generation from a WSDL.
+
+use crate::wsdl::{parse, Operation, SimpleType, Type, TypeAttribute, Wsdl};
+use case::CaseExt;
+use proc_macro2::{Ident, Literal, Span, TokenStream};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::{fs::File, io::Write};
+
+#[derive(Debug)]
+pub enum GenError {
+    Io(std::io::Error),
+    Wsdl(crate::wsdl::WsdlError),
+    /// No output directory was given, and `OUT_DIR` is not set.
+    MissingOutDir,
+    /// The WSDL declares a type the generator cannot handle.
+    UnsupportedType(String),
+    /// An allowed operation is not declared in the WSDL.
+    UnknownOperation(String),
+    /// The operation has no input message.
+    MissingInput(String),
+    /// The operation declares faults, which are not supported yet.
+    UnsupportedFaults(String),
+    /// A derive, type or visibility setting is not valid Rust.
+    InvalidTokens(String),
+}
+
+impl fmt::Display for GenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenError::Io(e) => write!(f, "{}", e),
+            GenError::Wsdl(e) => write!(f, "invalid WSDL: {}", e),
+            GenError::MissingOutDir => {
+                write!(f, "no output directory was given, and OUT_DIR is not set")
+            }
+            GenError::UnsupportedType(name) => write!(f, "unsupported type `{}`", name),
+            GenError::UnknownOperation(name) => {
+                write!(f, "operation `{}` is not declared in the WSDL", name)
+            }
+            GenError::MissingInput(name) => {
+                write!(f, "operation `{}` has no input message", name)
+            }
+            GenError::UnsupportedFaults(name) => write!(
+                f,
+                "operation `{}` declares faults, which are not supported",
+                name
+            ),
+            GenError::InvalidTokens(tokens) => write!(f, "`{}` is not valid Rust", tokens),
+        }
+    }
+}
+
+impl std::error::Error for GenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GenError::Io(e) => Some(e),
+            GenError::Wsdl(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for GenError {
+    fn from(e: std::io::Error) -> Self {
+        GenError::Io(e)
+    }
+}
+
+impl From<crate::wsdl::WsdlError> for GenError {
+    fn from(e: crate::wsdl::WsdlError) -> Self {
+        GenError::Wsdl(e)
+    }
+}
+
+/// Code generator.
+///
+/// ```rust,no_run
+/// savon_gen::gen::Generator::new()
+///     .with_output("stock_quote.rs")
+///     .with_module("stock_quote")
+///     .with_derive("PartialEq")
+///     .with_blocking(true)
+///     .write("assets/example.wsdl")
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct Generator {
+    out_dir: Option<PathBuf>,
+    output: Option<String>,
+    module: Option<String>,
+    derives: Vec<String>,
+    visibility: String,
+    types: HashMap<String, String>,
+    operations: Vec<String>,
+    rerun_if_changed: bool,
+    blocking: bool,
+    server: bool,
+    serde: bool,
+    strict_types: bool,
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        Generator {
+            out_dir: None,
+            output: None,
+            module: None,
+            derives: Vec::new(),
+            visibility: "pub".to_string(),
+            types: HashMap::new(),
+            operations: Vec::new(),
+            rerun_if_changed: true,
+            blocking: false,
+            server: false,
+            serde: false,
+            strict_types: false,
+        }
+    }
+}
+
+/// Generator settings, parsed into tokens.
+struct Tokens {
+    visibility: TokenStream,
+    derives: Vec<TokenStream>,
+    types: HashMap<String, TokenStream>,
+    module: Option<Ident>,
+}
+
+fn parse_tokens(s: &str) -> Result<TokenStream, GenError> {
+    s.parse()
+        .map_err(|_| GenError::InvalidTokens(s.to_string()))
+}
+
+impl Generator {
+    pub fn new() -> Self {
+        Generator::default()
+    }
+
+    /// Directory the code is written to, `OUT_DIR` by default.
+    pub fn with_out_dir<P: Into<PathBuf>>(mut self, out_dir: P) -> Self {
+        self.out_dir = Some(out_dir.into());
+        self
+    }
+
+    /// Name of the generated file, the WSDL file name with a `.rs` extension
+    /// by default.
+    pub fn with_output<S: Into<String>>(mut self, output: S) -> Self {
+        self.output = Some(output.into());
+        self
+    }
+
+    /// Wrap the generated code in a module.
+    pub fn with_module<S: Into<String>>(mut self, module: S) -> Self {
+        self.module = Some(module.into());
+        self
+    }
+
+    /// Add a derive to the generated types and messages, like
+    /// `"PartialEq"` or `"serde::Serialize"`.
+    pub fn with_derive<S: Into<String>>(mut self, derive: S) -> Self {
+        self.derives.push(derive.into());
+        self
+    }
+
+    /// Visibility of the generated items, `"pub"` by default.
+    pub fn with_visibility<S: Into<String>>(mut self, visibility: S) -> Self {
+        self.visibility = visibility.into();
+        self
+    }
+
+    /// Map an XSD type, by local name, to a Rust type instead of the
+    /// default mapping or the generated struct. The values are read and
+    /// written as text, so the type must implement `FromStr` and `Display`.
+    pub fn with_type<N: Into<String>, T: Into<String>>(
+        mut self,
+        xsd_type: N,
+        rust_type: T,
+    ) -> Self {
+        self.types.insert(xsd_type.into(), rust_type.into());
+        self
+    }
+
+    /// Only generate the given operation, and the other allowed ones. All
+    /// operations are generated by default.
+    pub fn with_operation<S: Into<String>>(mut self, operation: S) -> Self {
+        self.operations.push(operation.into());
+        self
+    }
+
+    /// Print `cargo:rerun-if-changed` for the WSDL when writing, enabled by
+    /// default.
+    pub fn with_rerun_if_changed(mut self, rerun_if_changed: bool) -> Self {
+        self.rerun_if_changed = rerun_if_changed;
+        self
+    }
+
+    /// Also generate a synchronous client, named after the service with a
+    /// `Blocking` suffix. The generated code requires the `blocking` feature.
+    pub fn with_blocking(mut self, blocking: bool) -> Self {
+        self.blocking = blocking;
+        self
+    }
+
+    /// Also generate a server trait, named after the port type, and a
+    /// `{PortType}Server` implementing `savon::server::Service`.
+    pub fn with_server(mut self, server: bool) -> Self {
+        self.server = server;
+        self
+    }
+
+    /// Derive `Serialize` and `Deserialize` on the generated types, with the
+    /// XML names. The generated code requires the `serde` feature.
+    pub fn with_serde(mut self, serde: bool) -> Self {
+        self.serde = serde;
+        self
+    }
+
+    /// Reject simple values whose `xsi:type` does not match the schema. By
+    /// default, values are decoded with the schema type and `xsi:type` is
+    /// ignored.
+    pub fn with_strict_types(mut self, strict_types: bool) -> Self {
+        self.strict_types = strict_types;
+        self
+    }
+
+    /// Generate the code for the WSDL file at `path`, and write it to the
+    /// output directory. Returns the path of the generated file.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf, GenError> {
+        let path = path.as_ref();
+        let out_dir = match &self.out_dir {
+            Some(out_dir) => out_dir.clone(),
+            None => std::env::var_os("OUT_DIR")
+                .map(PathBuf::from)
+                .ok_or(GenError::MissingOutDir)?,
+        };
+        let output = match &self.output {
+            Some(output) => out_dir.join(output),
+            None => out_dir.join(path.with_extension("rs").file_name().unwrap_or_default()),
+        };
+
+        if self.rerun_if_changed {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+        let wsdl = parse(&std::fs::read(path)?[..])?;
+        let generated = self.render(&wsdl, &format!("`{}`", path.display()))?;
+        let mut file = File::create(&output)?;
+        file.write_all(generated.as_bytes())?;
+        file.flush()?;
+
+        Ok(output)
+    }
+
+    /// Generate the code for a WSDL.
+    pub fn generate(&self, wsdl: &Wsdl) -> Result<String, GenError> {
+        self.render(wsdl, &format!("the `{}` WSDL", wsdl.name))
+    }
+
+    /// Generate the formatted code, with a header naming the source.
+    fn render(&self, wsdl: &Wsdl, source: &str) -> Result<String, GenError> {
+        let file = syn::parse2::<syn::File>(self.generate_tokens(wsdl)?)
+            .map_err(|e| GenError::InvalidTokens(e.to_string()))?;
+
+        Ok(format!(
+            "// This file is @generated by savon {} from {}.\n// Do not edit it by hand.\n\n{}",
+            env!("CARGO_PKG_VERSION"),
+            source,
+            prettyplease::unparse(&file)
+        ))
+    }
+
+    /// Generate the code for a WSDL, as tokens.
+    pub fn generate_tokens(&self, wsdl: &Wsdl) -> Result<TokenStream, GenError> {
+        if let Some(operation) = self
+            .operations
+            .iter()
+            .find(|o| !wsdl.operations.contains_key(*o))
+        {
+            return Err(GenError::UnknownOperation(operation.clone()));
+        }
+
+        gen_code(self, &self.tokens()?, wsdl)
+    }
+
+    fn tokens(&self) -> Result<Tokens, GenError> {
+        Ok(Tokens {
+            visibility: parse_tokens(&self.visibility)?,
+            derives: self
+                .derives
+                .iter()
+                .map(|d| parse_tokens(d))
+                .collect::<Result<_, _>>()?,
+            types: self
+                .types
+                .iter()
+                .map(|(name, t)| Ok((name.clone(), parse_tokens(t)?)))
+                .collect::<Result<_, GenError>>()?,
+            module: match self.module.as_ref() {
+                Some(module) if is_ident(module) => Some(Ident::new(module, Span::call_site())),
+                Some(module) => return Err(GenError::InvalidTokens(module.clone())),
+                None => None,
+            },
+        })
+    }
+
+    fn includes(&self, operation: &str) -> bool {
+        self.operations.is_empty() || self.operations.iter().any(|o| o == operation)
+    }
+}
+
+pub fn gen(wsdl: &Wsdl) -> Result<String, GenError> {
+    Generator::new().generate(wsdl)
+}
+
+fn is_ident(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Number of values of a field.
+#[derive(Clone, Copy)]
+enum Occurs {
+    One,
+    /// `minOccurs="0"`.
+    Optional,
+    /// Required but nillable, `None` is sent as `xsi:nil`.
+    Nillable,
+    /// `maxOccurs` above 1.
+    Repeated,
+}
+
+impl From<&TypeAttribute> for Occurs {
+    fn from(attributes: &TypeAttribute) -> Self {
+        if attributes.is_repeated() {
+            Occurs::Repeated
+        } else if attributes.is_optional() {
+            Occurs::Optional
+        } else if attributes.nillable {
+            Occurs::Nillable
+        } else {
+            Occurs::One
+        }
+    }
+}
+
+/// Rust type of a single value.
+fn rust_type(tokens: &Tokens, field_type: &SimpleType) -> TokenStream {
+    match field_type {
+        t if tokens.types.contains_key(t.xsd_name()) => tokens.types[t.xsd_name()].clone(),
+        SimpleType::Boolean => quote! { bool },
+        SimpleType::String => quote! { String },
+        SimpleType::Float => quote! { f64 },
+        SimpleType::Int => quote! { i64 },
+        SimpleType::DateTime => {
+            quote! { savon::internal::chrono::DateTime<savon::internal::chrono::offset::Utc> }
+        }
+        SimpleType::Base64Binary => quote! { Vec<u8> },
+        SimpleType::Complex(s) => {
+            let ft = Ident::new(&s.to_camel(), Span::call_site());
+            quote! { #ft }
+        }
+    }
+}
+
+fn input_message<'a>(name: &str, operation: &'a Operation) -> Result<&'a String, GenError> {
+    operation
+        .input
+        .as_ref()
+        .ok_or_else(|| GenError::MissingInput(name.to_string()))
+}
+
+fn gen_operations(
+    generator: &Generator,
+    tokens: &Tokens,
+    wsdl: &Wsdl,
+    blocking: bool,
+) -> Result<Vec<TokenStream>, GenError> {
+    let vis = &tokens.visibility;
+    let target_namespace = Literal::string(&wsdl.target_namespace);
+    let port_type = Literal::string(&wsdl.port_type);
+    let (asyncness, dot_await, http) = if blocking {
+        (quote! {}, quote! {}, quote! { savon::http::blocking })
+    } else {
+        (quote! { async }, quote! { .await }, quote! { savon::http })
+    };
+
+    wsdl.operations.iter().filter(|(name, _)| generator.includes(name)).map(|(name, operation)| {
+        let op_name = Ident::new(&name.to_snake(), Span::call_site());
+        let input = input_message(name, operation)?;
+        let input_name = Ident::new(&input.to_snake(), Span::call_site());
+        let input_type = Ident::new(&input.to_camel(), Span::call_site());
+
+        let op_str = Literal::string(name);
+        let input_str = Literal::string(&operation.input_name);
+        let soap_action = match operation.soap_action.as_ref() {
+            Some(action) => {
+                let action = Literal::string(action);
+                quote! { Some(#action) }
+            }
+            None => quote! { None },
+        };
+        let call = quote! {
+            &savon::http::Call {
+                base_url: &self.base_url,
+                namespace: #target_namespace,
+                port_type: #port_type,
+                operation: #op_str,
+                input: #input_str,
+                soap_action: #soap_action,
+                mtom: self.mtom.is_enabled(#op_str),
+                retry: self.retries.policy(#op_str),
+                interceptors: &self.interceptors,
+                options: &options,
+            }
+        };
+        let op_with_options = Ident::new(&format!("{}_with_options", name.to_snake()), Span::call_site());
+
+        // operations with a MIME binding get a variant sending and returning
+        // attachments
+        let has_attachments = !operation.input_attachments.is_empty()
+            || !operation.output_attachments.is_empty();
+        let op_with_attachments = Ident::new(&format!("{}_with_attachments", name.to_snake()), Span::call_site());
+
+        Ok(match (operation.output.as_ref(), operation.faults.as_ref()) {
+            (None, None) => {
+                let with_attachments = if has_attachments {
+                    quote! {
+                        #vis #asyncness fn #op_with_attachments(&self, #input_name: #input_type, attachments: Vec<savon::mime::Attachment>) -> Result<(), savon::Error> {
+                            let options = savon::http::CallOptions::default();
+                            #http::one_way_with_attachments(&self.transport, #call, &#input_name, attachments)#dot_await
+                        }
+                    }
+                } else {
+                    quote! {}
+                };
+
+                quote! {
+                    #vis #asyncness fn #op_name(&self, #input_name: #input_type) -> Result<(), savon::Error> {
+                        self.#op_with_options(#input_name, savon::http::CallOptions::default())#dot_await
+                    }
+
+                    #vis #asyncness fn #op_with_options(&self, #input_name: #input_type, options: savon::http::CallOptions) -> Result<(), savon::Error> {
+                        #http::one_way(&self.transport, #call, &#input_name)#dot_await
+                    }
+
+                    #with_attachments
+                }
+            },
+            (Some(out), None) => {
+                let out_name = Ident::new(out, Span::call_site());
+                let with_attachments = if has_attachments {
+                    quote! {
+                        #vis #asyncness fn #op_with_attachments(&self, #input_name: #input_type, attachments: Vec<savon::mime::Attachment>) -> Result<Result<savon::http::Reply<#out_name>, ()>, savon::Error> {
+                            let options = savon::http::CallOptions::default();
+                            #http::request_response_with_attachments(&self.transport, #call, &#input_name, attachments)#dot_await
+                        }
+                    }
+                } else {
+                    quote! {}
+                };
+
+                quote! {
+                    #vis #asyncness fn #op_name(&self, #input_name: #input_type) -> Result<Result<#out_name, ()>, savon::Error> {
+                        self.#op_with_options(#input_name, savon::http::CallOptions::default())#dot_await
+                    }
+
+                    #vis #asyncness fn #op_with_options(&self, #input_name: #input_type, options: savon::http::CallOptions) -> Result<Result<#out_name, ()>, savon::Error> {
+                        #http::request_response(&self.transport, #call, &#input_name)#dot_await
+                    }
+
+                    #with_attachments
+                }
+            },
+            (_, Some(_)) => return Err(GenError::UnsupportedFaults(name.clone())),
+        })
+    }).collect::<Result<Vec<_>, _>>()
+}
+
+fn gen_server(
+    generator: &Generator,
+    tokens: &Tokens,
+    wsdl: &Wsdl,
+) -> Result<TokenStream, GenError> {
+    let vis = &tokens.visibility;
+    let target_namespace = Literal::string(&wsdl.target_namespace);
+    let trait_name = Ident::new(&wsdl.port_type.to_camel(), Span::call_site());
+    let server_name = Ident::new(
+        &format!("{}Server", wsdl.port_type.to_camel()),
+        Span::call_site(),
+    );
+
+    let mut methods = Vec::new();
+    let mut operations = Vec::new();
+    let mut calls = Vec::new();
+    for (name, operation) in wsdl
+        .operations
+        .iter()
+        .filter(|(name, _)| generator.includes(name))
+    {
+        let op_name = Ident::new(&name.to_snake(), Span::call_site());
+        let input = input_message(name, operation)?;
+        let input_name = Ident::new(&input.to_snake(), Span::call_site());
+        let input_type = Ident::new(&input.to_camel(), Span::call_site());
+        let op_str = Literal::string(name);
+        let soap_action = match operation.soap_action.as_ref() {
+            Some(action) => {
+                let action = Literal::string(action);
+                quote! { Some(#action) }
+            }
+            None => quote! { None },
+        };
+
+        let (output, serialize) = match operation.output.as_ref() {
+            Some(out) => {
+                let out_name = Ident::new(out, Span::call_site());
+                (
+                    quote! { #out_name },
+                    quote! { Ok(savon::gen::ToElements::to_elements(&output)) },
+                )
+            }
+            None => (quote! { () }, quote! { { let () = output; Ok(vec![]) } }),
+        };
+        let one_way = operation.output.is_none();
+
+        methods.push(quote! {
+            async fn #op_name(&self, #input_name: #input_type) -> Result<#output, savon::server::Fault>;
+        });
+        operations.push(quote! {
+            savon::server::Operation {
+                name: #op_str,
+                soap_action: #soap_action,
+                one_way: #one_way,
+            },
+        });
+        calls.push(quote! {
+            #op_str => {
+                let input = <#input_type as savon::gen::FromElement>::from_element(input)
+                    .map_err(|e| savon::server::Fault::client(format!("invalid input: {:?}", e)))?;
+                let output = self.0.#op_name(input).await?;
+                #serialize
+            }
+        });
+    }
+
+    let server_doc = format!(
+        "Serves a `{}` implementation with a `savon::server::Dispatcher`.",
+        wsdl.port_type.to_camel()
+    );
+
+    Ok(quote! {
+        #[allow(dead_code)]
+        #[savon::internal::async_trait::async_trait]
+        #vis trait #trait_name: Send + Sync {
+            #(#methods)*
+        }
+        #[doc = #server_doc]
+        #[allow(dead_code)]
+        #vis struct #server_name<S>(pub S);
+
+        #[savon::internal::async_trait::async_trait]
+        impl<S: #trait_name> savon::server::Service for #server_name<S> {
+            fn namespace(&self) -> &'static str {
+                #target_namespace
+            }
+
+            fn operations(&self) -> &'static [savon::server::Operation] {
+                &[#(#operations)*]
+            }
+
+            async fn call(&self, operation: &str, input: &xmltree::Element) -> Result<Vec<xmltree::Element>, savon::server::Fault> {
+                match operation {
+                    #(#calls)*
+                    _ => Err(savon::server::Fault::client(format!("unknown operation {}", operation))),
+                }
+            }
+        }
+    })
+}
+
+fn gen_code(generator: &Generator, tokens: &Tokens, wsdl: &Wsdl) -> Result<TokenStream, GenError> {
+    let vis = &tokens.visibility;
+    let derives = &tokens.derives;
+    let (serde_derives, serde) = if generator.serde {
+        (
+            quote! { savon::internal::serde::Serialize, savon::internal::serde::Deserialize, },
+            quote! { #[serde(crate = "savon::internal::serde")] },
+        )
+    } else {
+        (quote! {}, quote! {})
+    };
+    let operations = gen_operations(generator, tokens, wsdl, false)?;
+
+    let types = wsdl
+        .types
+        .iter()
+        .filter(|(name, _)| !tokens.types.contains_key(*name))
+        .map(|(name, t)| {
+            if let Type::Complex(c) = t {
+                let type_name = Ident::new(&name.to_camel(), Span::call_site());
+
+                let fields = c
+                    .fields
+                    .iter()
+                    .map(|(field_name, (attributes, field_type))| {
+                        let fname = Ident::new(&field_name.to_snake(), Span::call_site());
+                        let ft = rust_type(tokens, field_type);
+                        let occurs = Occurs::from(attributes);
+                        let ft = match occurs {
+                            Occurs::One => ft,
+                            Occurs::Optional | Occurs::Nillable => quote! { Option<#ft> },
+                            Occurs::Repeated => quote! { Vec<#ft> },
+                        };
+
+                        let mut serde_attributes = Vec::new();
+                        if generator.serde && fname != field_name {
+                            serde_attributes.push(quote! { rename = #field_name });
+                        }
+                        if generator.serde {
+                            match occurs {
+                                Occurs::One => {}
+                                Occurs::Optional | Occurs::Nillable => serde_attributes.push(quote! { default, skip_serializing_if = "Option::is_none" }),
+                                Occurs::Repeated => serde_attributes.push(quote! { default, skip_serializing_if = "Vec::is_empty" }),
+                            }
+                        }
+                        let serde_attributes = if serde_attributes.is_empty() {
+                            quote! {}
+                        } else {
+                            quote! { #[serde(#(#serde_attributes),*)] }
+                        };
+
+                        quote! {
+                            #serde_attributes
+                            #vis #fname: #ft,
+                        }
+                    })
+                    .collect::<Vec<_>>();
+
+                let fields_serialize_impl = c
+                    .fields
+                    .iter()
+                    .map(|(field_name, (attributes, field_type))| {
+                        let fname = Ident::new(&field_name.to_snake(), Span::call_site());
+                        let ftype = Literal::string(field_name);
+                        let prefix = quote! { xmltree::Element::node(#ftype) };
+
+                        // `v` is a reference to the value
+                        let element = match field_type {
+                            t if tokens.types.contains_key(t.xsd_name()) => quote! { #prefix.with_text(v.to_string()) },
+                            SimpleType::Complex(_) => quote! { #prefix.with_children(v.to_elements()) },
+                            SimpleType::Base64Binary => quote! { savon::mtom::binary_element(#ftype, v) },
+                            _ => quote! { #prefix.with_text(v.to_string()) },
+                        };
+
+                        // each field is chained to the elements iterator, absent
+                        // optional values are omitted and nillable ones are sent
+                        // as `xsi:nil`
+                        match Occurs::from(attributes) {
+                            Occurs::One => quote! { std::iter::once(&self.#fname).map(|v| #element) },
+                            Occurs::Optional | Occurs::Repeated => quote! { self.#fname.iter().map(|v| #element) },
+                            Occurs::Nillable => quote! {
+                                std::iter::once(match &self.#fname {
+                                    Some(v) => #element,
+                                    None => #prefix.with_attr("xsi:nil", "true"),
+                                })
+                            },
+                        }
+                    })
+                    .collect::<Vec<_>>();
+
+                let serialize_impl = if fields_serialize_impl.is_empty() {
+                    quote! {
+                        impl savon::gen::ToElements for #type_name {
+                            fn to_elements(&self) -> Vec<xmltree::Element> {
+                                vec![]
+                            }
+                        }
+                    }
+
+                }else {
+                    quote! {
+                        impl savon::gen::ToElements for #type_name {
+                            fn to_elements(&self) -> Vec<xmltree::Element> {
+                                std::iter::empty()
+                                    #(.chain(#fields_serialize_impl))*
+                                    .collect()
+                            }
+                        }
+                    }
+                };
+
+                let fields_deserialize_impl = c
+                    .fields
+                    .iter()
+                    .map(|(field_name, (attributes, field_type))| {
+                        let fname = Ident::new(&field_name.to_snake(), Span::call_site());
+                        let ftype = Literal::string(field_name);
+
+                        // reads the value from `e`, a `&xmltree::Element`
+                        let value = match field_type {
+                            t if tokens.types.contains_key(t.xsd_name()) => {
+                                let rust_type = &tokens.types[t.xsd_name()];
+                                let expected_type = Literal::string(&rust_type.to_string());
+                                quote! {{
+                                    let text = e.get_text().unwrap_or_default();
+                                    text.parse::<#rust_type>().map_err(|_| savon::Error::from(savon::rpser::xml::Error::ExpectedElementWithType {
+                                        name: e.name.clone(),
+                                        expected_type: #expected_type.to_string(),
+                                        given: Some(text.to_string()),
+                                    }))
+                                }}
+                            },
+                            SimpleType::Boolean => quote! { e.as_boolean().map_err(savon::Error::from) },
+                            SimpleType::String => quote! { e.as_string().map_err(savon::Error::from) },
+                            SimpleType::Float => quote! { e.as_double().map_err(savon::Error::from) },
+                            SimpleType::Int => quote! { e.as_long().map_err(savon::Error::from) },
+                            SimpleType::DateTime => quote! { e.as_datetime().map_err(savon::Error::from) },
+                            SimpleType::Base64Binary => quote! { e.as_binary().map_err(savon::Error::from) },
+                            SimpleType::Complex(s) => {
+                                let complex_type = Ident::new(&s.to_camel(), Span::call_site());
+                                quote! { #complex_type::from_element(e) }
+                            },
+                        };
+
+                        // with strict types, a mismatching `xsi:type` is an error
+                        let value = match field_type {
+                            SimpleType::Complex(_) if !tokens.types.contains_key(field_type.xsd_name()) => value,
+                            t if generator.strict_types => {
+                                let xsd_type = t.xsd_name();
+                                quote! { e.expect_type(#xsd_type).map_err(savon::Error::from).and_then(|_| #value) }
+                            }
+                            _ => value,
+                        };
+
+                        match Occurs::from(attributes) {
+                            Occurs::One => quote! {
+                                #fname: savon::gen::field(element, #ftype).and_then(|e| #value)?,
+                            },
+                            Occurs::Optional | Occurs::Nillable => quote! {
+                                #fname: element
+                                    .get_child(#ftype)
+                                    .filter(|e| !e.is_nil())
+                                    .map(|e| #value)
+                                    .transpose()?,
+                            },
+                            Occurs::Repeated => quote! {
+                                #fname: savon::gen::repeated_field(element, #ftype)
+                                    .filter(|e| !e.is_nil())
+                                    .map(|e| #value)
+                                    .collect::<Result<_, savon::Error>>()?,
+                            },
+                        }
+                    })
+                    .collect::<Vec<_>>();
+
+                    let deserialize_impl = if fields_deserialize_impl.is_empty() {
+                        quote! {
+                            impl savon::gen::FromElement for #type_name {
+                                fn from_element(_element: &xmltree::Element) -> Result<Self, savon::Error> {
+                                    Ok(#type_name {
+                                    })
+                                }
+                            }
+                        }
+                    } else {
+                        quote! {
+                            impl savon::gen::FromElement for #type_name {
+                                fn from_element(element: &xmltree::Element) -> Result<Self, savon::Error> {
+                                    Ok(#type_name {
+                                        #(#fields_deserialize_impl)*
+                                    })
+                                }
+                            }
+                        }
+                    };
+
+                Ok(quote! {
+                    #[derive(Clone, Debug, Default, #serde_derives #(#derives),*)]
+                    #serde
+                    #vis struct #type_name {
+                        #(#fields)*
+                    }
+
+                    #serialize_impl
+
+                    #deserialize_impl
+                })
+            } else {
+                Err(GenError::UnsupportedType(name.clone()))
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let messages = wsdl
+        .messages
+        .iter()
+        .map(|(message_name, message)| {
+            let mname = Ident::new(message_name, Span::call_site());
+            let iname = Ident::new(&message.part_element, Span::call_site());
+
+            quote! {
+                #[derive(Clone, Debug, Default, #serde_derives #(#derives),*)]
+                #serde
+                #vis struct #mname(#vis #iname);
+
+                impl savon::gen::ToElements for #mname {
+                    fn to_elements(&self) -> Vec<xmltree::Element> {
+                        self.0.to_elements()
+                    }
+                }
+
+                impl savon::gen::FromElement for #mname {
+                    fn from_element(element: &xmltree::Element) -> Result<Self, savon::Error> {
+                        #iname::from_element(element).map(#mname)
+                    }
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let service_name = Ident::new(&wsdl.name, Span::call_site());
+
+    let toks = quote! {
+        use savon::internal::xmltree;
+        use savon::rpser::xml::*;
+
+        #(#types)*
+
+        #vis struct #service_name<T = savon::transport::ReqwestTransport> {
+            #vis base_url: String,
+            #vis transport: T,
+            #vis interceptors: savon::interceptor::Chain,
+            #vis mtom: savon::mtom::MtomPolicy,
+            #vis retries: savon::retry::Retries,
+        }
+        #(#messages)*
+
+        #[allow(dead_code)]
+        impl #service_name {
+            #vis fn new(base_url: String) -> Self {
+                Self::with_client(base_url, savon::internal::reqwest::Client::new())
+            }
+
+            #vis fn with_client(base_url: String, client: savon::internal::reqwest::Client) -> Self {
+                Self::with_transport(base_url, savon::transport::ReqwestTransport::new(client))
+            }
+        }
+
+        #[allow(dead_code)]
+        impl<T: savon::transport::Transport> #service_name<T> {
+            #vis fn with_transport(base_url: String, transport: T) -> Self {
+                #service_name {
+                    base_url,
+                    transport,
+                    interceptors: savon::interceptor::Chain::new(),
+                    mtom: savon::mtom::MtomPolicy::default(),
+                    retries: savon::retry::Retries::default(),
+                }
+            }
+
+            /// Add an interceptor at the end of the client's interceptor chain.
+            #vis fn with_interceptor<I: savon::interceptor::Interceptor + 'static>(mut self, interceptor: I) -> Self {
+                self.interceptors.push(interceptor);
+                self
+            }
+
+            /// Set when binary content is sent with MTOM.
+            #vis fn with_mtom(mut self, policy: savon::mtom::MtomPolicy) -> Self {
+                self.mtom = policy;
+                self
+            }
+
+            /// Set which operations are retried, and how.
+            #vis fn with_retries(mut self, retries: savon::retry::Retries) -> Self {
+                self.retries = retries;
+                self
+            }
+
+            #(#operations)*
+        }
+    };
+
+    let blocking_client = if generator.blocking {
+        let blocking_name = Ident::new(&format!("{}Blocking", wsdl.name), Span::call_site());
+        let blocking_operations = gen_operations(generator, tokens, wsdl, true)?;
+
+        quote! {
+            #vis struct #blocking_name<T = savon::transport::blocking::ReqwestTransport> {
+                #vis base_url: String,
+                #vis transport: T,
+                #vis interceptors: savon::interceptor::Chain,
+                #vis mtom: savon::mtom::MtomPolicy,
+                #vis retries: savon::retry::Retries,
+            }
+
+            #[allow(dead_code)]
+            impl #blocking_name {
+                #vis fn new(base_url: String) -> Self {
+                    Self::with_client(base_url, savon::internal::reqwest::blocking::Client::new())
+                }
+
+                #vis fn with_client(base_url: String, client: savon::internal::reqwest::blocking::Client) -> Self {
+                    Self::with_transport(base_url, savon::transport::blocking::ReqwestTransport::new(client))
+                }
+            }
+
+            #[allow(dead_code)]
+            impl<T: savon::transport::blocking::Transport> #blocking_name<T> {
+                #vis fn with_transport(base_url: String, transport: T) -> Self {
+                    #blocking_name {
+                        base_url,
+                        transport,
+                        interceptors: savon::interceptor::Chain::new(),
+                        mtom: savon::mtom::MtomPolicy::default(),
+                        retries: savon::retry::Retries::default(),
+                    }
+                }
+
+                /// Add an interceptor at the end of the client's interceptor chain.
+                #vis fn with_interceptor<I: savon::interceptor::Interceptor + 'static>(mut self, interceptor: I) -> Self {
+                    self.interceptors.push(interceptor);
+                    self
+                }
+
+                /// Set when binary content is sent with MTOM.
+                #vis fn with_mtom(mut self, policy: savon::mtom::MtomPolicy) -> Self {
+                    self.mtom = policy;
+                    self
+                }
+
+                /// Set which operations are retried, and how.
+                #vis fn with_retries(mut self, retries: savon::retry::Retries) -> Self {
+                    self.retries = retries;
+                    self
+                }
+
+                #(#blocking_operations)*
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let mut stream: TokenStream = toks;
+    stream.extend(blocking_client);
+    if generator.server {
+        stream.extend(gen_server(generator, tokens, wsdl)?);
+    }
+
+    if let Some(module) = tokens.module.as_ref() {
+        stream = quote! {
+            #vis mod #module {
+                #stream
+            }
+        };
+    }
+
+    Ok(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wsdl::Occurence;
+    const EXAMPLE_WSDL: &[u8] = include_bytes!("../../assets/example.wsdl");
+    const ATTACHMENTS_WSDL: &[u8] = include_bytes!("../../assets/attachments.wsdl");
+
+    #[test]
+    fn example() {
+        let wsdl = parse(EXAMPLE_WSDL).unwrap();
+        println!("wsdl: {:?}", wsdl);
+
+        let res = gen(&wsdl).unwrap();
+
+        println!("generated:\n{}", res);
+        assert!(res.contains("pub struct StockQuoteService"));
+        assert!(res.contains("pub async fn get_last_trade_price"));
+    }
+
+    #[test]
+    fn blocking_client() {
+        let wsdl = parse(EXAMPLE_WSDL).unwrap();
+
+        let res = gen(&wsdl).unwrap();
+        assert!(!res.contains("StockQuoteServiceBlocking"));
+
+        let res = Generator::new()
+            .with_blocking(true)
+            .generate(&wsdl)
+            .unwrap();
+        assert!(res.contains("pub struct StockQuoteServiceBlocking"));
+        assert!(res.contains("savon::http::blocking::request_response"));
+    }
+
+    #[test]
+    fn binary_fields() {
+        let mut wsdl = parse(EXAMPLE_WSDL).unwrap();
+        if let Some(Type::Complex(c)) = wsdl.types.get_mut("TradePriceRequest") {
+            c.fields.push((
+                "document".to_string(),
+                (TypeAttribute::default(), SimpleType::Base64Binary),
+            ));
+        }
+
+        let res = gen(&wsdl).unwrap();
+        assert!(res.contains("pub document: Vec<u8>"));
+        assert!(res.contains("savon::mtom::binary_element(\"document\", v)"));
+        assert!(res.contains("as_binary()"));
+        assert!(res.contains("mtom: self.mtom.is_enabled(\"GetLastTradePrice\")"));
+        assert!(res.contains("retry: self.retries.policy(\"GetLastTradePrice\")"));
+    }
+
+    #[test]
+    fn attachments() {
+        let res = gen(&parse(EXAMPLE_WSDL).unwrap()).unwrap();
+        assert!(!res.contains("with_attachments"));
+
+        let res = gen(&parse(ATTACHMENTS_WSDL).unwrap()).unwrap();
+        assert!(res.contains("pub async fn get_company_info_with_attachments"));
+        assert!(res.contains("savon::http::Reply<GetCompanyInfoOutput>"));
+    }
+
+    #[test]
+    fn call_options() {
+        let res = gen(&parse(EXAMPLE_WSDL).unwrap()).unwrap();
+        assert!(res.contains("pub async fn get_last_trade_price_with_options"));
+        assert!(res.contains("options: savon::http::CallOptions"));
+        assert!(res.contains("options: &options"));
+        assert!(res.contains("port_type: \"StockQuotePortType\""));
+        assert!(res.contains("input: \"GetLastTradePriceRequest\""));
+    }
+
+    #[test]
+    fn server() {
+        let wsdl = parse(EXAMPLE_WSDL).unwrap();
+        assert!(!gen(&wsdl).unwrap().contains("trait StockQuotePortType"));
+
+        let res = Generator::new().with_server(true).generate(&wsdl).unwrap();
+        assert!(res.contains("pub trait StockQuotePortType: Send + Sync"));
+        assert!(res.contains("    async fn get_last_trade_price(\n        &self,\n        get_last_trade_price_input: GetLastTradePriceInput,\n    ) -> Result<GetLastTradePriceOutput, savon::server::Fault>;"));
+        assert!(res.contains(
+            "impl<S: StockQuotePortType> savon::server::Service for StockQuotePortTypeServer<S>"
+        ));
+        assert!(res.contains("soap_action: Some(\"http://example.com/GetLastTradePrice\")"));
+    }
+
+    #[test]
+    fn generator() {
+        let mut wsdl = parse(EXAMPLE_WSDL).unwrap();
+        let mut operation = wsdl.operations["GetLastTradePrice"].clone();
+        operation.name = "GetTradeHistory".to_string();
+        wsdl.operations
+            .insert("GetTradeHistory".to_string(), operation);
+
+        let res = Generator::new()
+            .with_module("quotes")
+            .with_derive("PartialEq")
+            .with_visibility("pub(crate)")
+            .with_type("float", "rust_decimal::Decimal")
+            .with_operation("GetLastTradePrice")
+            .generate(&wsdl)
+            .unwrap();
+        assert!(res.contains("\n\npub(crate) mod quotes {\n"));
+        assert!(res.contains(
+            "    #[derive(Clone, Debug, Default, PartialEq)]\n    pub(crate) struct TradePrice {"
+        ));
+        assert!(res.contains("pub(crate) price: rust_decimal::Decimal"));
+        assert!(res.contains("parse::<rust_decimal::Decimal>()"));
+        assert!(res.contains("pub(crate) async fn get_last_trade_price"));
+        assert!(!res.contains("fn get_trade_history"));
+
+        let err = Generator::new().with_operation("Unknown").generate(&wsdl);
+        assert!(matches!(err, Err(GenError::UnknownOperation(o)) if o == "Unknown"));
+        let err = Generator::new().with_derive("Partial Eq (").generate(&wsdl);
+        assert!(matches!(err, Err(GenError::InvalidTokens(_))));
+        let err = Generator::new().with_module("stock-quote").generate(&wsdl);
+        assert!(matches!(err, Err(GenError::InvalidTokens(_))));
+    }
+
+    #[test]
+    fn output_only_operations() {
+        let mut wsdl = parse(EXAMPLE_WSDL).unwrap();
+        wsdl.operations.get_mut("GetLastTradePrice").unwrap().input = None;
+
+        let err = gen(&wsdl);
+        assert!(matches!(err, Err(GenError::MissingInput(o)) if o == "GetLastTradePrice"));
+    }
+
+    #[test]
+    fn operations_with_faults() {
+        let mut wsdl = parse(EXAMPLE_WSDL).unwrap();
+        wsdl.operations.get_mut("GetLastTradePrice").unwrap().faults =
+            Some(vec!["UnknownSymbol".to_string()]);
+
+        let err = gen(&wsdl);
+        assert!(matches!(err, Err(GenError::UnsupportedFaults(o)) if o == "GetLastTradePrice"));
+    }
+
+    #[test]
+    fn write() {
+        let out_dir = std::env::temp_dir().join(format!("savon-gen-{}", std::process::id()));
+        std::fs::create_dir_all(&out_dir).unwrap();
+
+        let generator = Generator::new()
+            .with_out_dir(&out_dir)
+            .with_rerun_if_changed(false);
+        let path = generator.write("../assets/example.wsdl").unwrap();
+        assert_eq!(path, out_dir.join("example.rs"));
+        let path = generator
+            .with_output("quotes.rs")
+            .write("../assets/example.wsdl")
+            .unwrap();
+        assert_eq!(path, out_dir.join("quotes.rs"));
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .contains("pub struct StockQuoteService"));
+
+        std::fs::remove_dir_all(&out_dir).unwrap();
+    }
+
+    #[test]
+    fn deterministic() {
+        const QUOTES_WSDL: &str = r#"<?xml version="1.0"?>
+<definitions name="Quotes" targetNamespace="urn:quotes" xmlns:tns="urn:quotes"
+             xmlns:xsd1="urn:quotes" xmlns:soap="http://schemas.xmlsoap.org/wsdl/soap/"
+             xmlns="http://schemas.xmlsoap.org/wsdl/">
+  <types>
+    <schema targetNamespace="urn:quotes" xmlns="http://www.w3.org/2001/XMLSchema">
+      <element name="Quote">
+        <complexType><all>
+          <element name="symbol" type="string"/>
+          <element name="exchange" type="string"/>
+          <element name="currency" type="string"/>
+          <element name="bid" type="float"/>
+        </all></complexType>
+      </element>
+      <element name="Symbol">
+        <complexType><all><element name="symbol" type="string"/></all></complexType>
+      </element>
+    </schema>
+  </types>
+  <message name="GetQuoteInput"><part name="body" element="xsd1:Symbol"/></message>
+  <message name="GetQuoteOutput"><part name="body" element="xsd1:Quote"/></message>
+  <message name="WatchInput"><part name="body" element="xsd1:Symbol"/></message>
+  <message name="AddInput"><part name="body" element="xsd1:Quote"/></message>
+  <portType name="QuotesPortType">
+    <operation name="Watch"><input message="tns:WatchInput"/></operation>
+    <operation name="GetQuote">
+      <input message="tns:GetQuoteInput"/>
+      <output message="tns:GetQuoteOutput"/>
+    </operation>
+    <operation name="Add"><input message="tns:AddInput"/></operation>
+  </portType>
+  <binding name="QuotesBinding" type="tns:QuotesPortType">
+    <soap:binding style="document" transport="http://schemas.xmlsoap.org/soap/http"/>
+  </binding>
+  <service name="QuotesService">
+    <port name="QuotesPort" binding="tns:QuotesBinding">
+      <soap:address location="http://example.com/quotes"/>
+    </port>
+  </service>
+</definitions>"#;
+
+        let generator = Generator::new().with_blocking(true).with_server(true);
+        let first = generator
+            .generate(&parse(QUOTES_WSDL.as_bytes()).unwrap())
+            .unwrap();
+        for _ in 0..10 {
+            let res = generator
+                .generate(&parse(QUOTES_WSDL.as_bytes()).unwrap())
+                .unwrap();
+            assert_eq!(res, first);
+        }
+
+        // fields in document order
+        let position = |s: &str| first.find(s).unwrap();
+        assert!(position("pub symbol: String,\n    pub exchange") < position("pub currency"));
+        assert!(position("pub currency") < position("pub bid"));
+        assert!(position("fn add(") < position("fn get_quote("));
+        assert!(position("fn get_quote(") < position("fn watch("));
+    }
+
+    #[test]
+    fn occurs() {
+        let mut wsdl = parse(EXAMPLE_WSDL).unwrap();
+        let field = |name: &str, min_occurs, max_occurs, nillable| {
+            (
+                name.to_string(),
+                (
+                    TypeAttribute {
+                        nillable,
+                        min_occurs,
+                        max_occurs,
+                    },
+                    SimpleType::String,
+                ),
+            )
+        };
+        if let Some(Type::Complex(c)) = wsdl.types.get_mut("TradePrice") {
+            c.fields
+                .push(field("note", Some(Occurence::Num(0)), None, false));
+            c.fields
+                .push(field("tag", None, Some(Occurence::Unbounded), false));
+            c.fields.push(field(
+                "alias",
+                Some(Occurence::Num(0)),
+                Some(Occurence::Num(3)),
+                true,
+            ));
+            c.fields.push(field(
+                "single",
+                Some(Occurence::Num(1)),
+                Some(Occurence::Num(1)),
+                false,
+            ));
+        }
+
+        let res = gen(&wsdl).unwrap();
+        assert!(res.contains("pub note: Option<String>,"));
+        assert!(res.contains("pub tag: Vec<String>,"));
+        assert!(res.contains("pub alias: Vec<String>,"));
+        assert!(res.contains("pub single: String,"));
+        assert!(res.contains("savon::gen::repeated_field(element, \"tag\")"));
+        assert!(res.contains(".map(|v| xmltree::Element::node(\"tag\").with_text(v.to_string())),"));
+    }
+
+    #[test]
+    fn nil() {
+        let mut wsdl = parse(EXAMPLE_WSDL).unwrap();
+        if let Some(Type::Complex(c)) = wsdl.types.get_mut("TradePrice") {
+            c.fields.push((
+                "note".to_string(),
+                (
+                    TypeAttribute {
+                        nillable: true,
+                        ..TypeAttribute::default()
+                    },
+                    SimpleType::String,
+                ),
+            ));
+        }
+
+        let res = gen(&wsdl).unwrap();
+        assert!(res.contains("pub note: Option<String>,"));
+        assert!(res.contains("xmltree::Element::node(\"note\").with_attr(\"xsi:nil\", \"true\")"));
+        assert!(res.contains(".filter(|e| !e.is_nil())"));
+    }
+
+    #[test]
+    fn strict_types() {
+        let res = gen(&parse(EXAMPLE_WSDL).unwrap()).unwrap();
+        assert!(!res.contains("expect_type"));
+        assert!(res.contains("e.as_double()"));
+        assert!(res.contains("e.as_string()"));
+
+        let res = Generator::new()
+            .with_strict_types(true)
+            .generate(&parse(EXAMPLE_WSDL).unwrap())
+            .unwrap();
+        assert!(res.contains(".expect_type(\"float\")"));
+        assert!(res.contains(".expect_type(\"string\")"));
+    }
+
+    #[test]
+    fn serde() {
+        let mut wsdl = parse(EXAMPLE_WSDL).unwrap();
+        if let Some(Type::Complex(c)) = wsdl.types.get_mut("TradePrice") {
+            c.fields.push((
+                "tradeTime".to_string(),
+                (
+                    TypeAttribute {
+                        nillable: true,
+                        ..TypeAttribute::default()
+                    },
+                    SimpleType::DateTime,
+                ),
+            ));
+        }
+        assert!(!gen(&wsdl).unwrap().contains("serde"));
+
+        let res = Generator::new().with_serde(true).generate(&wsdl).unwrap();
+        assert!(res.contains(
+            "#[derive(
+    Clone,
+    Debug,
+    Default,
+    savon::internal::serde::Serialize,
+    savon::internal::serde::Deserialize,
+)]
+#[serde(crate = \"savon::internal::serde\")]
+pub struct TradePrice {
+    pub price: f64,
+    #[serde(rename = \"tradeTime\", default, skip_serializing_if = \"Option::is_none\")]
+    pub trade_time: Option<"
+        ));
+        assert!(res.contains(
+            "#[serde(crate = \"savon::internal::serde\")]
+pub struct GetLastTradePriceOutput(pub TradePrice);"
+        ));
+    }
+}
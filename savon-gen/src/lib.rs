@@ -0,0 +1,12 @@
+//! WSDL parser and code generator for savon.
+//!
+//! This crate is shared by savon and the `savon::wsdl!` macro. Use it
+//! through `savon::gen` and `savon::wsdl`.
+
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate quote;
+
+pub mod gen;
+pub mod wsdl;
@@ -0,0 +1,884 @@
+//! WSDL inspection helpers.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use xmltree::Element;
+
+#[derive(Debug)]
+pub enum WsdlError {
+    Parse(xmltree::ParseError),
+    ElementNotFound(&'static str),
+    AttributeNotFound(&'static str),
+    NotAnElement,
+    Empty,
+    /// A `minOccurs` or `maxOccurs` value is neither a number nor `unbounded`.
+    InvalidOccurence(String),
+    /// A schema type is not a complex type.
+    UnsupportedType(String),
+    /// A message has no part.
+    EmptyMessage(String),
+}
+
+impl fmt::Display for WsdlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WsdlError::Parse(e) => write!(f, "invalid XML: {}", e),
+            WsdlError::ElementNotFound(name) => write!(f, "missing `{}` element", name),
+            WsdlError::AttributeNotFound(name) => write!(f, "missing `{}` attribute", name),
+            WsdlError::NotAnElement => write!(f, "expected an element"),
+            WsdlError::Empty => write!(f, "unexpected empty element"),
+            WsdlError::InvalidOccurence(value) => write!(
+                f,
+                "invalid occurence `{}`, expected a number or `unbounded`",
+                value
+            ),
+            WsdlError::UnsupportedType(name) => {
+                write!(f, "type `{}` is not a complex type", name)
+            }
+            WsdlError::EmptyMessage(name) => write!(f, "message `{}` has no part", name),
+        }
+    }
+}
+
+impl std::error::Error for WsdlError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WsdlError::Parse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<xmltree::ParseError> for WsdlError {
+    fn from(error: xmltree::ParseError) -> Self {
+        WsdlError::Parse(error)
+    }
+}
+
+/// WSDL document.
+#[derive(Debug)]
+pub struct Wsdl {
+    pub name: String,
+    /// Name of the `portType`.
+    pub port_type: String,
+    pub target_namespace: String,
+    pub types: BTreeMap<String, Type>,
+    pub messages: BTreeMap<String, Message>,
+    pub operations: BTreeMap<String, Operation>,
+    /// Ports of the service.
+    pub ports: Vec<Port>,
+}
+
+/// `port` of a `service`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Port {
+    pub name: String,
+    /// Name of the binding.
+    pub binding: String,
+    /// `soap:address` location.
+    pub address: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum SimpleType {
+    Boolean,
+    String,
+    Float,
+    Int,
+    DateTime,
+    Base64Binary,
+    Complex(String),
+}
+
+impl SimpleType {
+    /// Local name of the XSD type, as used by `Generator::with_type`.
+    pub fn xsd_name(&self) -> &str {
+        match self {
+            SimpleType::Boolean => "boolean",
+            SimpleType::String => "string",
+            SimpleType::Float => "float",
+            SimpleType::Int => "int",
+            SimpleType::DateTime => "dateTime",
+            SimpleType::Base64Binary => "base64Binary",
+            SimpleType::Complex(name) => name,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Occurence {
+    Unbounded,
+    Num(u32),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TypeAttribute {
+    pub nillable: bool,
+    pub min_occurs: Option<Occurence>,
+    pub max_occurs: Option<Occurence>,
+}
+
+impl TypeAttribute {
+    /// Whether the element can appear more than once, from `maxOccurs`.
+    pub fn is_repeated(&self) -> bool {
+        match self.max_occurs {
+            Some(Occurence::Unbounded) => true,
+            Some(Occurence::Num(n)) => n > 1,
+            None => false,
+        }
+    }
+
+    /// Whether the element can be absent, from `minOccurs`.
+    pub fn is_optional(&self) -> bool {
+        matches!(self.min_occurs, Some(Occurence::Num(0)))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ComplexType {
+    /// Fields, in document order.
+    pub fields: Vec<(String, (TypeAttribute, SimpleType))>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Type {
+    Simple(SimpleType),
+    Complex(ComplexType),
+}
+
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub part_name: String,
+    pub part_element: String,
+}
+
+/// Attachment part of a `mime:multipartRelated` binding.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MimePart {
+    /// Name of the message part sent as an attachment.
+    pub part: String,
+    /// Accepted content types.
+    pub content_types: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Operation {
+    pub name: String,
+    pub input: Option<String>,
+    /// Name of the input in the port type, `{operation}Request` (or
+    /// `{operation}` for one-way operations) if it is not set.
+    pub input_name: String,
+    pub output: Option<String>,
+    pub faults: Option<Vec<String>>,
+    /// `soapAction` of the operation, from the SOAP binding.
+    pub soap_action: Option<String>,
+    /// Attachments of the input message, from the MIME binding.
+    pub input_attachments: Vec<MimePart>,
+    /// Attachments of the output message, from the MIME binding.
+    pub output_attachments: Vec<MimePart>,
+}
+
+//FIXME: splitting the namespace is the naive way, we should keep the namespace
+// and check for collisions instead
+fn split_namespace(s: &str) -> &str {
+    match s.find(':') {
+        None => s,
+        Some(index) => &s[index + 1..],
+    }
+}
+
+/// Read the attachment parts of a `mime:multipartRelated` binding. The part
+/// holding the `soap:body` is not an attachment.
+fn mime_parts(message: &Element) -> Vec<MimePart> {
+    let mut parts: Vec<MimePart> = Vec::new();
+
+    let multipart = match message.get_child("multipartRelated") {
+        Some(multipart) => multipart,
+        None => return parts,
+    };
+
+    for content in multipart
+        .children
+        .iter()
+        .filter_map(|c| c.as_element())
+        .filter(|c| c.name == "part")
+        .flat_map(|p| p.children.iter().filter_map(|c| c.as_element()))
+        .filter(|c| c.name == "content")
+    {
+        let part = match content.attributes.get("part") {
+            Some(part) => part,
+            None => continue,
+        };
+        let content_type = content.attributes.get("type").cloned();
+
+        match parts.iter_mut().find(|p| &p.part == part) {
+            Some(existing) => existing.content_types.extend(content_type),
+            None => parts.push(MimePart {
+                part: part.to_string(),
+                content_types: content_type.into_iter().collect(),
+            }),
+        }
+    }
+
+    parts
+}
+
+pub fn parse(bytes: &[u8]) -> Result<Wsdl, WsdlError> {
+    let mut types = BTreeMap::new();
+    let mut messages = BTreeMap::new();
+    let mut operations = BTreeMap::new();
+
+    let elements = Element::parse(bytes)?;
+    trace!("elements: {:#?}", elements);
+    let target_namespace = elements
+        .attributes
+        .get("targetNamespace")
+        .ok_or(WsdlError::AttributeNotFound("targetNamespace"))?
+        .to_string();
+
+    let types_el = elements
+        .get_child("types")
+        .ok_or(WsdlError::ElementNotFound("types"))?
+        .children
+        .iter()
+        .filter_map(|c| c.as_element())
+        .next()
+        .ok_or(WsdlError::Empty)?;
+
+    for elem in types_el.children.iter().filter_map(|c| c.as_element()) {
+        trace!("type: {:#?}", elem);
+        let name = elem
+            .attributes
+            .get("name")
+            .ok_or(WsdlError::AttributeNotFound("name"))?;
+
+        // sometimes we have <element name="TypeName"><complexType>...</complexType></element>,
+        // sometimes we have <complexType name="TypeName">...</complexType>
+        //let current_child = elem.children.get(0).ok_or(WsdlError::Empty)?
+        //    .as_element().ok_or(WsdlError::NotAnElement)?;
+
+        let child = if elem.name == "complexType" {
+            elem
+        } else {
+            elem.children
+                .first()
+                .ok_or(WsdlError::Empty)?
+                .as_element()
+                .ok_or(WsdlError::NotAnElement)?
+        };
+
+        if child.name == "complexType" {
+            let mut fields = Vec::new();
+            for field in child
+                .children
+                .first()
+                .ok_or(WsdlError::Empty)?
+                .as_element()
+                .ok_or(WsdlError::NotAnElement)?
+                .children
+                .iter()
+                .filter_map(|c| c.as_element())
+            {
+                let field_name = field
+                    .attributes
+                    .get("name")
+                    .ok_or(WsdlError::AttributeNotFound("name"))?;
+                let field_type = field
+                    .attributes
+                    .get("type")
+                    .ok_or(WsdlError::AttributeNotFound("type"))?;
+                let nillable = match field.attributes.get("nillable").map(|s| s.as_str()) {
+                    Some("true") => true,
+                    Some("false") => false,
+                    _ => false,
+                };
+
+                let min_occurs = match field.attributes.get("minOccurs").map(|s| s.as_str()) {
+                    None => None,
+                    Some("unbounded") => Some(Occurence::Unbounded),
+                    Some(n) => Some(Occurence::Num(
+                        n.parse()
+                            .map_err(|_| WsdlError::InvalidOccurence(n.to_string()))?,
+                    )),
+                };
+                let max_occurs = match field.attributes.get("maxOccurs").map(|s| s.as_str()) {
+                    None => None,
+                    Some("unbounded") => Some(Occurence::Unbounded),
+                    Some(n) => Some(Occurence::Num(
+                        n.parse()
+                            .map_err(|_| WsdlError::InvalidOccurence(n.to_string()))?,
+                    )),
+                };
+                trace!("field {:?} -> {:?}", field_name, field_type);
+                let type_attributes = TypeAttribute {
+                    nillable,
+                    min_occurs,
+                    max_occurs,
+                };
+
+                let simple_type = match split_namespace(field_type.as_str()) {
+                    "boolean" => SimpleType::Boolean,
+                    "string" => SimpleType::String,
+                    "int" => SimpleType::Int,
+                    "float" => SimpleType::Float,
+                    "dateTime" => SimpleType::DateTime,
+                    "base64Binary" => SimpleType::Base64Binary,
+                    s => SimpleType::Complex(s.to_string()),
+                };
+                fields.push((field_name.to_string(), (type_attributes, simple_type)));
+            }
+
+            types.insert(name.to_string(), Type::Complex(ComplexType { fields }));
+        } else {
+            trace!("child {:#?}", child);
+            return Err(WsdlError::UnsupportedType(name.to_string()));
+        }
+    }
+
+    for message in elements
+        .children
+        .iter()
+        .filter_map(|c| c.as_element())
+        .filter(|c| c.name == "message")
+    {
+        trace!("message: {:#?}", message);
+        let name = message
+            .attributes
+            .get("name")
+            .ok_or(WsdlError::AttributeNotFound("name"))?;
+        let c = message
+            .children
+            .iter()
+            .filter_map(|c| c.as_element())
+            .next()
+            .ok_or_else(|| WsdlError::EmptyMessage(name.to_string()))?;
+        //FIXME: namespace
+        let part_name = c
+            .attributes
+            .get("name")
+            .ok_or(WsdlError::AttributeNotFound("name"))?
+            .to_string();
+        let part_element = split_namespace(
+            c.attributes
+                .get("element")
+                .ok_or(WsdlError::AttributeNotFound("element"))?,
+        )
+        .to_string();
+
+        messages.insert(
+            name.to_string(),
+            Message {
+                part_name,
+                part_element,
+            },
+        );
+    }
+
+    let port_type_el = elements
+        .get_child("portType")
+        .ok_or(WsdlError::ElementNotFound("portType"))?;
+    let port_type = port_type_el
+        .attributes
+        .get("name")
+        .ok_or(WsdlError::AttributeNotFound("name"))?
+        .to_string();
+
+    for operation in port_type_el.children.iter().filter_map(|c| c.as_element()) {
+        let operation_name = operation
+            .attributes
+            .get("name")
+            .ok_or(WsdlError::AttributeNotFound("name"))?;
+
+        let mut input = None;
+        let mut input_name = None;
+        let mut output = None;
+        let mut faults = None;
+        for child in operation
+            .children
+            .iter()
+            .filter_map(|c| c.as_element())
+            .filter(|c| c.attributes.contains_key("message"))
+        {
+            let message = split_namespace(
+                child
+                    .attributes
+                    .get("message")
+                    .ok_or(WsdlError::AttributeNotFound("message"))?,
+            );
+            // FIXME: not testing for unicity
+            match child.name.as_str() {
+                "input" => {
+                    input = Some(message.to_string());
+                    input_name = child.attributes.get("name").cloned();
+                }
+                "output" => output = Some(message.to_string()),
+                "fault" => {
+                    if faults.is_none() {
+                        faults = Some(Vec::new());
+                    }
+                    if let Some(v) = faults.as_mut() {
+                        v.push(message.to_string());
+                    }
+                }
+                _ => return Err(WsdlError::ElementNotFound("operation member")),
+            }
+        }
+
+        // default names from the WSDL 1.1 specification, section 2.4.5
+        let input_name = input_name.unwrap_or_else(|| match output {
+            Some(_) => format!("{}Request", operation_name),
+            None => operation_name.to_string(),
+        });
+
+        operations.insert(
+            operation_name.to_string(),
+            Operation {
+                name: operation_name.to_string(),
+                input,
+                input_name,
+                output,
+                faults,
+                soap_action: None,
+                input_attachments: vec![],
+                output_attachments: vec![],
+            },
+        );
+    }
+
+    //FIXME: only reading the soapAction and MIME parts of bindings for now
+    for binding_operation in elements
+        .children
+        .iter()
+        .filter_map(|c| c.as_element())
+        .filter(|c| c.name == "binding")
+        .flat_map(|b| b.children.iter().filter_map(|c| c.as_element()))
+        .filter(|c| c.name == "operation")
+    {
+        let operation_name = binding_operation
+            .attributes
+            .get("name")
+            .ok_or(WsdlError::AttributeNotFound("name"))?;
+        let soap_action = binding_operation
+            .get_child("operation")
+            .and_then(|o| o.attributes.get("soapAction"))
+            .filter(|a| !a.is_empty());
+
+        if let Some(operation) = operations.get_mut(operation_name) {
+            operation.soap_action = soap_action.map(|a| a.to_string());
+            if let Some(input) = binding_operation.get_child("input") {
+                operation.input_attachments = mime_parts(input);
+            }
+            if let Some(output) = binding_operation.get_child("output") {
+                operation.output_attachments = mime_parts(output);
+            }
+        }
+    }
+
+    let service = elements
+        .get_child("service")
+        .ok_or(WsdlError::ElementNotFound("service"))?;
+    let service_name = service
+        .attributes
+        .get("name")
+        .ok_or(WsdlError::AttributeNotFound("name"))?;
+    let ports = service
+        .children
+        .iter()
+        .filter_map(|c| c.as_element())
+        .filter(|c| c.name == "port")
+        .map(|port| Port {
+            name: port.attributes.get("name").cloned().unwrap_or_default(),
+            binding: split_namespace(port.attributes.get("binding").map_or("", |b| b.as_str()))
+                .to_string(),
+            address: port
+                .get_child("address")
+                .and_then(|a| a.attributes.get("location"))
+                .cloned(),
+        })
+        .collect();
+
+    debug!("service name: {}", service_name);
+    debug!("parsed types: {:#?}", types);
+    debug!("parsed messages: {:#?}", messages);
+    debug!("parsed operations: {:#?}", operations);
+
+    Ok(Wsdl {
+        name: service_name.to_string(),
+        port_type,
+        target_namespace,
+        types,
+        messages,
+        operations,
+        ports,
+    })
+}
+
+/// Construct of a WSDL that savon does not support.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unsupported {
+    /// Where the construct is, like `types/TradePrice/price`.
+    pub location: String,
+    pub message: String,
+}
+
+const BUILTIN_TYPES: &[&str] = &[
+    "boolean",
+    "string",
+    "int",
+    "float",
+    "dateTime",
+    "base64Binary",
+];
+
+fn children<'a>(element: &'a Element, name: &str) -> Vec<&'a Element> {
+    element
+        .children
+        .iter()
+        .filter_map(|c| c.as_element())
+        .filter(|c| c.name == name)
+        .collect()
+}
+
+/// List the constructs of the document that `parse` or the generator do not
+/// support, or only partially.
+pub fn validate(bytes: &[u8]) -> Result<Vec<Unsupported>, WsdlError> {
+    let elements = Element::parse(bytes)?;
+    let mut issues = Vec::new();
+    let mut report = |location: String, message: &str| {
+        issues.push(Unsupported {
+            location,
+            message: message.to_string(),
+        })
+    };
+    let name = |e: &Element| e.attributes.get("name").cloned().unwrap_or_default();
+
+    let schemas = elements
+        .get_child("types")
+        .map(|t| t.children.iter().filter_map(|c| c.as_element()).collect())
+        .unwrap_or_else(Vec::new);
+    if schemas.len() > 1 {
+        report("types".to_string(), "only the first schema is read");
+    }
+    let mut defined = Vec::new();
+    let mut fields = Vec::new();
+    for elem in schemas
+        .first()
+        .map(|s| s.children.iter().filter_map(|c| c.as_element()).collect())
+        .unwrap_or_else(Vec::new)
+    {
+        let location = format!("types/{}", name(elem));
+        let complex = match elem.name.as_str() {
+            "complexType" => Some(elem),
+            "element" => elem
+                .children
+                .iter()
+                .filter_map(|c| c.as_element())
+                .next()
+                .filter(|c| c.name == "complexType"),
+            _ => None,
+        };
+        let complex = match complex {
+            Some(complex) => complex,
+            None if elem.name == "element" && elem.attributes.contains_key("type") => {
+                report(
+                    location,
+                    "elements referencing a named type are not supported",
+                );
+                continue;
+            }
+            None => {
+                report(
+                    location,
+                    &format!("`{}` is not supported, only complex types are", elem.name),
+                );
+                continue;
+            }
+        };
+        defined.push(name(elem));
+
+        let group = match complex
+            .children
+            .iter()
+            .filter_map(|c| c.as_element())
+            .next()
+        {
+            Some(group) => group,
+            None => continue,
+        };
+        if group.name != "sequence" && group.name != "all" {
+            report(
+                location.clone(),
+                &format!(
+                    "`{}` is not supported, only `sequence` and `all` are",
+                    group.name
+                ),
+            );
+        }
+        if complex
+            .children
+            .iter()
+            .filter_map(|c| c.as_element())
+            .count()
+            > 1
+        {
+            report(location.clone(), "attributes are not supported");
+        }
+        for field in group.children.iter().filter_map(|c| c.as_element()) {
+            let location = format!("{}/{}", location, name(field));
+            if field.name != "element" {
+                report(location, &format!("`{}` is not supported", field.name));
+                continue;
+            }
+            match field.attributes.get("type") {
+                Some(t) => fields.push((location.clone(), split_namespace(t).to_string())),
+                None => report(
+                    location.clone(),
+                    "fields without a `type` are not supported",
+                ),
+            }
+            for occurs in ["minOccurs", "maxOccurs"].iter() {
+                if let Some(value) = field.attributes.get(*occurs) {
+                    if value != "unbounded" && value.parse::<u32>().is_err() {
+                        report(location.clone(), &format!("invalid {} `{}`", occurs, value));
+                    }
+                }
+            }
+        }
+    }
+    for (location, field_type) in fields {
+        if !BUILTIN_TYPES.contains(&field_type.as_str()) && !defined.contains(&field_type) {
+            report(location, &format!("type `{}` is not supported", field_type));
+        }
+    }
+
+    for message in children(&elements, "message") {
+        let location = format!("message/{}", name(message));
+        // other parts can be attachments, from the MIME binding
+        let parts = children(message, "part");
+        if parts
+            .first()
+            .is_some_and(|p| !p.attributes.contains_key("element"))
+        {
+            report(
+                location,
+                "parts referencing a type instead of an element are not supported",
+            );
+        } else if parts
+            .iter()
+            .filter(|p| p.attributes.contains_key("element"))
+            .count()
+            > 1
+        {
+            report(location, "only the first part is read");
+        }
+    }
+
+    if children(&elements, "portType").len() > 1 {
+        report(
+            "portType".to_string(),
+            "only the first port type is generated",
+        );
+    }
+    for operation in elements
+        .get_child("portType")
+        .map(|p| children(p, "operation"))
+        .unwrap_or_else(Vec::new)
+    {
+        let location = format!("portType/{}", name(operation));
+        if operation.get_child("input").is_none() {
+            report(
+                location.clone(),
+                "operations without an input are not supported",
+            );
+        }
+        if operation.get_child("fault").is_some() {
+            report(location, "operations with faults are not supported");
+        }
+    }
+
+    for binding in children(&elements, "binding") {
+        let location = format!("binding/{}", name(binding));
+        if let Some(soap) = binding.get_child("binding") {
+            if soap.namespace.as_deref() == Some("http://schemas.xmlsoap.org/wsdl/soap12/") {
+                report(location.clone(), "SOAP 1.2 bindings are not supported");
+            }
+            if soap.attributes.get("style").map(|s| s.as_str()) == Some("rpc") {
+                report(location.clone(), "RPC style is not supported");
+            }
+        }
+        let encoded = children(binding, "operation").iter().any(|o| {
+            ["input", "output"].iter().any(|m| {
+                o.get_child(*m)
+                    .and_then(|m| m.get_child("body"))
+                    .and_then(|b| b.attributes.get("use"))
+                    .map(|u| u.as_str())
+                    == Some("encoded")
+            })
+        });
+        if encoded {
+            report(location, "SOAP encoding is not supported");
+        }
+    }
+
+    let services = children(&elements, "service");
+    if services.len() > 1 {
+        report("service".to_string(), "only the first service is generated");
+    }
+
+    Ok(issues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const EXAMPLE_WSDL: &[u8] = include_bytes!("../../assets/example.wsdl");
+    const ATTACHMENTS_WSDL: &[u8] = include_bytes!("../../assets/attachments.wsdl");
+
+    #[test]
+    fn parse_example() {
+        let res = parse(EXAMPLE_WSDL);
+        println!("res: {:?}", res);
+        res.unwrap();
+    }
+
+    #[test]
+    fn parse_errors() {
+        let example = std::str::from_utf8(EXAMPLE_WSDL).unwrap();
+        let parse_with = |from: &str, to: &str| parse(example.replacen(from, to, 1).as_bytes());
+
+        let res = parse_with(r#"type="string"/>"#, r#"type="string" maxOccurs="many"/>"#);
+        assert!(matches!(res, Err(WsdlError::InvalidOccurence(n)) if n == "many"));
+        let res = parse_with(
+            "<types>\n    <schema",
+            r#"<types><schema><simpleType name="Currency"><restriction base="string"/></simpleType></schema><schema"#,
+        );
+        assert!(matches!(res, Err(WsdlError::UnsupportedType(n)) if n == "Currency"));
+        let res = parse_with(r#"<part name="body" element="xsd1:TradePrice"/>"#, "");
+        assert!(matches!(res, Err(WsdlError::EmptyMessage(n)) if n == "GetLastTradePriceOutput"));
+    }
+
+    #[test]
+    fn soap_action() {
+        let wsdl = parse(EXAMPLE_WSDL).unwrap();
+
+        assert_eq!(
+            wsdl.operations["GetLastTradePrice"].soap_action.as_deref(),
+            Some("http://example.com/GetLastTradePrice")
+        );
+        assert_eq!(wsdl.port_type, "StockQuotePortType");
+    }
+
+    #[test]
+    fn input_names() {
+        let wsdl = parse(EXAMPLE_WSDL).unwrap();
+        assert_eq!(
+            wsdl.operations["GetLastTradePrice"].input_name,
+            "GetLastTradePriceRequest"
+        );
+
+        let named = String::from_utf8_lossy(EXAMPLE_WSDL).replace(
+            "<input message=\"tns:GetLastTradePriceInput\"/>",
+            "<input name=\"Quote\" message=\"tns:GetLastTradePriceInput\"/>",
+        );
+        let wsdl = parse(named.as_bytes()).unwrap();
+        assert_eq!(wsdl.operations["GetLastTradePrice"].input_name, "Quote");
+    }
+
+    #[test]
+    fn mime_binding() {
+        let wsdl = parse(ATTACHMENTS_WSDL).unwrap();
+        let operation = &wsdl.operations["GetCompanyInfo"];
+
+        assert!(operation.input_attachments.is_empty());
+        assert_eq!(
+            operation.output_attachments,
+            vec![
+                MimePart {
+                    part: "docs".to_string(),
+                    content_types: vec!["text/html".to_string()],
+                },
+                MimePart {
+                    part: "logo".to_string(),
+                    content_types: vec!["image/gif".to_string(), "image/jpeg".to_string()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn ports() {
+        let wsdl = parse(EXAMPLE_WSDL).unwrap();
+        assert_eq!(
+            wsdl.ports,
+            vec![Port {
+                name: "StockQuotePort".to_string(),
+                binding: "StockQuoteSoapBinding".to_string(),
+                address: Some("http://example.com/stockquote".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn unsupported() {
+        assert_eq!(validate(EXAMPLE_WSDL).unwrap(), vec![]);
+        assert_eq!(validate(ATTACHMENTS_WSDL).unwrap(), vec![]);
+
+        let wsdl = r#"<definitions xmlns="http://schemas.xmlsoap.org/wsdl/"
+             xmlns:soap12="http://schemas.xmlsoap.org/wsdl/soap12/"
+             xmlns:xsd="http://www.w3.org/2001/XMLSchema">
+  <types>
+    <xsd:schema>
+      <xsd:simpleType name="Currency"/>
+      <xsd:element name="Quote">
+        <xsd:complexType>
+          <xsd:choice>
+            <xsd:element name="price" type="xsd:decimal" maxOccurs="many"/>
+          </xsd:choice>
+        </xsd:complexType>
+      </xsd:element>
+    </xsd:schema>
+  </types>
+  <message name="GetQuote"><part name="symbol" type="xsd:string"/></message>
+  <portType name="QuotePortType">
+    <operation name="QuoteUpdated"><output message="GetQuote"/></operation>
+    <operation name="GetQuote">
+      <input message="GetQuote"/>
+      <output message="GetQuote"/>
+      <fault name="UnknownSymbol" message="GetQuote"/>
+    </operation>
+  </portType>
+  <binding name="QuoteBinding">
+    <soap12:binding style="rpc"/>
+  </binding>
+</definitions>"#;
+        let issues = validate(wsdl.as_bytes()).unwrap();
+        let locations = issues
+            .iter()
+            .map(|i| i.location.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            locations,
+            vec![
+                "types/Currency",
+                "types/Quote",
+                "types/Quote/price",
+                "types/Quote/price",
+                "message/GetQuote",
+                "portType/QuoteUpdated",
+                "portType/GetQuote",
+                "binding/QuoteBinding",
+                "binding/QuoteBinding",
+            ]
+        );
+        assert_eq!(issues[3].message, "type `decimal` is not supported");
+        assert_eq!(
+            issues[5].message,
+            "operations without an input are not supported"
+        );
+        assert_eq!(
+            issues[6].message,
+            "operations with faults are not supported"
+        );
+        assert_eq!(issues[7].message, "SOAP 1.2 bindings are not supported");
+    }
+}
@@ -0,0 +1,194 @@
+//! Procedural macros for savon, use them through `savon::wsdl!`.
+
+extern crate proc_macro;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use savon_gen::gen::Generator;
+use std::path::PathBuf;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{Expr, ExprLit, Lit, LitStr, Meta, Token};
+
+/// Generate the code for a WSDL, at compile time.
+///
+/// The path is relative to the crate's `Cargo.toml`. The options follow the
+/// `Generator` builder:
+///
+/// ```rust,ignore
+/// savon::wsdl!(
+///     "assets/example.wsdl",
+///     module = "soap",
+///     blocking,
+///     server,
+///     serde,
+///     strict_types,
+///     derive = "PartialEq",
+///     visibility = "pub(crate)",
+///     operation = "GetLastTradePrice",
+///     types("dateTime" = "String"),
+/// );
+/// ```
+#[proc_macro]
+pub fn wsdl(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    syn::parse_macro_input!(input as Input)
+        .expand()
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+struct Input {
+    path: LitStr,
+    options: Punctuated<Meta, Token![,]>,
+}
+
+impl Parse for Input {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let path = input.parse()?;
+        let options = if input.is_empty() {
+            Punctuated::new()
+        } else {
+            input.parse::<Token![,]>()?;
+            Punctuated::parse_terminated(input)?
+        };
+
+        Ok(Input { path, options })
+    }
+}
+
+/// `"xsdType" = "RustType"` in `types(...)`.
+struct TypeMapping {
+    xsd_type: LitStr,
+    rust_type: LitStr,
+}
+
+impl Parse for TypeMapping {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let xsd_type = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let rust_type = input.parse()?;
+
+        Ok(TypeMapping {
+            xsd_type,
+            rust_type,
+        })
+    }
+}
+
+fn string_value(meta: &Meta) -> syn::Result<String> {
+    match meta {
+        Meta::NameValue(syn::MetaNameValue {
+            value: Expr::Lit(ExprLit {
+                lit: Lit::Str(s), ..
+            }),
+            ..
+        }) => Ok(s.value()),
+        _ => Err(syn::Error::new_spanned(meta, "expected `name = \"value\"`")),
+    }
+}
+
+impl Input {
+    fn generator(&self) -> syn::Result<Generator> {
+        let mut generator = Generator::new();
+
+        for option in self.options.iter() {
+            let name = option
+                .path()
+                .get_ident()
+                .map(|i| i.to_string())
+                .unwrap_or_default();
+            generator = match (name.as_str(), option) {
+                ("blocking", Meta::Path(_)) => generator.with_blocking(true),
+                ("server", Meta::Path(_)) => generator.with_server(true),
+                ("serde", Meta::Path(_)) => generator.with_serde(true),
+                ("strict_types", Meta::Path(_)) => generator.with_strict_types(true),
+                ("module", _) => generator.with_module(string_value(option)?),
+                ("derive", _) => generator.with_derive(string_value(option)?),
+                ("visibility", _) => generator.with_visibility(string_value(option)?),
+                ("operation", _) => generator.with_operation(string_value(option)?),
+                ("types", Meta::List(list)) => {
+                    let mappings = list
+                        .parse_args_with(Punctuated::<TypeMapping, Token![,]>::parse_terminated)?;
+                    mappings.into_iter().fold(generator, |g, m| {
+                        g.with_type(m.xsd_type.value(), m.rust_type.value())
+                    })
+                }
+                _ => return Err(syn::Error::new_spanned(option, "unknown option")),
+            };
+        }
+
+        Ok(generator)
+    }
+
+    fn expand(&self) -> syn::Result<TokenStream> {
+        let generator = self.generator()?;
+
+        let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default());
+        path.push(self.path.value());
+        let error = |message: String| syn::Error::new(self.path.span(), message);
+
+        let content = std::fs::read(&path)
+            .map_err(|e| error(format!("cannot read {}: {}", path.display(), e)))?;
+        let wsdl = savon_gen::wsdl::parse(&content[..])
+            .map_err(|e| error(format!("invalid WSDL {}: {}", path.display(), e)))?;
+        let code = generator
+            .generate_tokens(&wsdl)
+            .map_err(|e| error(format!("cannot generate {}: {}", path.display(), e)))?;
+
+        // rebuild when the WSDL changes
+        let path = path.to_string_lossy();
+        Ok(quote! {
+            const _: &[u8] = include_bytes!(#path);
+            #code
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(input: TokenStream) -> syn::Result<TokenStream> {
+        syn::parse2::<Input>(input)?.expand()
+    }
+
+    #[test]
+    fn options() {
+        let code = expand(quote! {
+            "../assets/example.wsdl",
+            module = "soap",
+            blocking,
+            derive = "PartialEq",
+            types("float" = "String"),
+        })
+        .unwrap()
+        .to_string();
+        assert!(code.contains("include_bytes !"));
+        assert!(code.contains("pub mod soap"));
+        assert!(code.contains("pub struct StockQuoteServiceBlocking"));
+        assert!(code.contains("PartialEq"));
+        assert!(code.contains("pub price : String"));
+
+        let err = expand(quote! { "../assets/example.wsdl", blocking = true }).unwrap_err();
+        assert_eq!(err.to_string(), "unknown option");
+        let err = expand(quote! { "../assets/example.wsdl", module }).unwrap_err();
+        assert_eq!(err.to_string(), "expected `name = \"value\"`");
+        let err = expand(quote! { "../assets/example.wsdl", operation = "Unknown" }).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("operation `Unknown` is not declared in the WSDL"));
+    }
+
+    #[test]
+    fn invalid_wsdl() {
+        let err = expand(quote! { "missing.wsdl" }).unwrap_err();
+        assert!(err.to_string().starts_with("cannot read"));
+
+        let path = std::env::temp_dir().join(format!("savon-macros-{}.wsdl", std::process::id()));
+        std::fs::write(&path, "<definitions").unwrap();
+        let path = path.to_string_lossy().into_owned();
+        let err = expand(quote! { #path }).unwrap_err();
+        assert!(err.to_string().starts_with("invalid WSDL"));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,4 @@
+// paths are relative to the trybuild project, in `target/tests/trybuild`
+savon_macros::wsdl!("../../../../savon-macros/tests/ui/simple-type.wsdl");
+
+fn main() {}
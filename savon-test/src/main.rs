@@ -1,9 +1,13 @@
 #[macro_use]
 extern crate log;
 
-mod soap {
-    include!(concat!(env!("OUT_DIR"), "/example.rs"));
-}
+savon::wsdl!(
+    "countrinfoservice.wsdl",
+    module = "soap",
+    blocking,
+    server,
+    serde
+);
 
 #[tokio::main]
 async fn main() -> Result<(), savon::Error> {
@@ -0,0 +1,472 @@
+//! WS-Addressing headers and message correlation.
+//!
+//! The `Addressing` interceptor adds `wsa:Action`, `wsa:To`, `wsa:MessageID`
+//! and `wsa:ReplyTo` to every request. When replies are sent asynchronously
+//! to another endpoint, a `Correlator` keeps track of the requests waiting
+//! for a reply, and matches incoming messages by their `wsa:RelatesTo`:
+//!
+//! ```rust,ignore
+//! let correlator = Correlator::new();
+//! let client = soap::StockQuoteService::new(url).with_interceptor(
+//!     Addressing::new()
+//!         .with_reply_to("http://me.example.com/replies")
+//!         .with_correlator(correlator.clone()),
+//! );
+//!
+//! // later, when a message is received on the reply endpoint
+//! let headers = Headers::from_xml(&body)?;
+//! if let Some(request) = correlator.correlate(&headers) {
+//!     println!("reply to {}", request.operation);
+//! }
+//! ```
+
+use crate::interceptor::{Interceptor, RequestContext};
+use crate::rpser::xml::BuildElement;
+use crate::rpser::RpcError;
+use crate::transport::HttpResponse;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use xmltree::{Element, XMLNode};
+
+pub const WSA_NS: &str = "http://www.w3.org/2005/08/addressing";
+/// Address used in `wsa:ReplyTo` when the reply is sent in the HTTP response.
+pub const ANONYMOUS: &str = "http://www.w3.org/2005/08/addressing/anonymous";
+/// Time after which a request without a reply is forgotten by a `Correlator`.
+pub const DEFAULT_EXPIRY: Duration = Duration::from_secs(60 * 60);
+
+/// Generate a random `urn:uuid:` message ID.
+pub fn message_id() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    // UUID version 4, RFC 4122 variant
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    format!(
+        "urn:uuid:{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Addressing headers of a message.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Headers {
+    pub action: Option<String>,
+    pub to: Option<String>,
+    pub message_id: Option<String>,
+    /// Address of the `wsa:ReplyTo` endpoint reference.
+    pub reply_to: Option<String>,
+    pub relates_to: Option<String>,
+}
+
+impl Headers {
+    /// Read the addressing headers from the content of a `soap:Header`, as
+    /// found in `rpser::Response::header`.
+    pub fn from_header(header: &Element) -> Self {
+        let mut headers = Headers::default();
+
+        for child in header
+            .children
+            .iter()
+            .filter_map(|c| c.as_element())
+            .filter(|c| c.namespace.as_deref() == Some(WSA_NS))
+        {
+            let text = child.get_text().map(|t| t.trim().to_string());
+            match child.name.as_str() {
+                "Action" => headers.action = text,
+                "To" => headers.to = text,
+                "MessageID" => headers.message_id = text,
+                "RelatesTo" => headers.relates_to = text,
+                "ReplyTo" => {
+                    headers.reply_to = child
+                        .get_child("Address")
+                        .and_then(|a| a.get_text())
+                        .map(|t| t.trim().to_string())
+                }
+                _ => {}
+            }
+        }
+
+        headers
+    }
+
+    /// Read the addressing headers of an envelope.
+    pub fn from_xml(xml: &str) -> Result<Self, crate::Error> {
+        let envelope = Element::parse(xml.as_bytes()).map_err(|e| RpcError::InvalidXml {
+            error: e.to_string(),
+        })?;
+
+        Ok(envelope
+            .get_child("Header")
+            .map(Headers::from_header)
+            .unwrap_or_default())
+    }
+
+    /// Build the header elements.
+    pub fn to_elements(&self) -> Vec<Element> {
+        let node = |name: &str, text: &str| {
+            Element::node(format!("wsa:{}", name))
+                .with_attr("xmlns:wsa", WSA_NS)
+                .with_text(text)
+        };
+
+        let mut elements = Vec::new();
+        if let Some(action) = self.action.as_ref() {
+            elements.push(node("Action", action));
+        }
+        if let Some(to) = self.to.as_ref() {
+            elements.push(node("To", to));
+        }
+        if let Some(message_id) = self.message_id.as_ref() {
+            elements.push(node("MessageID", message_id));
+        }
+        if let Some(reply_to) = self.reply_to.as_ref() {
+            elements.push(
+                Element::node("wsa:ReplyTo")
+                    .with_attr("xmlns:wsa", WSA_NS)
+                    .with_child(Element::node("wsa:Address").with_text(reply_to.clone())),
+            );
+        }
+        if let Some(relates_to) = self.relates_to.as_ref() {
+            elements.push(node("RelatesTo", relates_to));
+        }
+        elements
+    }
+}
+
+/// Request waiting for an asynchronous reply.
+#[derive(Clone, Debug)]
+pub struct Pending {
+    pub message_id: String,
+    pub operation: String,
+    pub sent: Instant,
+}
+
+/// Matches replies to the requests they relate to.
+///
+/// Clones share the same list of pending requests. Requests that failed are
+/// removed by the `Addressing` interceptor, and requests still waiting for
+/// a reply after the expiry delay are dropped.
+#[derive(Clone, Debug)]
+pub struct Correlator {
+    pending: Arc<Mutex<HashMap<String, Pending>>>,
+    expiry: Duration,
+}
+
+impl Default for Correlator {
+    fn default() -> Self {
+        Correlator {
+            pending: Arc::default(),
+            expiry: DEFAULT_EXPIRY,
+        }
+    }
+}
+
+impl Correlator {
+    pub fn new() -> Self {
+        Correlator::default()
+    }
+
+    /// Time after which a request without a reply is forgotten.
+    pub fn with_expiry(mut self, expiry: Duration) -> Self {
+        self.expiry = expiry;
+        self
+    }
+
+    /// Record a request waiting for a reply, and drop the expired ones.
+    pub fn register<S: Into<String>>(&self, message_id: S, operation: &str) {
+        let message_id = message_id.into();
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, p| p.sent.elapsed() < self.expiry);
+        pending.insert(
+            message_id.clone(),
+            Pending {
+                message_id,
+                operation: operation.to_string(),
+                sent: Instant::now(),
+            },
+        );
+    }
+
+    /// Stop waiting for a reply to a request.
+    pub fn forget(&self, message_id: &str) -> Option<Pending> {
+        self.pending.lock().unwrap().remove(message_id)
+    }
+
+    /// Find and remove the request a message relates to.
+    pub fn correlate(&self, headers: &Headers) -> Option<Pending> {
+        let relates_to = headers.relates_to.as_ref()?;
+        self.pending.lock().unwrap().remove(relates_to)
+    }
+
+    /// Number of requests still waiting for a reply.
+    pub fn len(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Action of a request without an explicit one, from the WS-Addressing
+/// WSDL binding: `{targetNamespace}/{portType}/{input}`, with `:` as the
+/// delimiter if the namespace is a URN.
+pub fn default_action(context: &RequestContext) -> String {
+    let delimiter = if context.namespace.starts_with("urn:") {
+        ":"
+    } else {
+        "/"
+    };
+    let namespace = context.namespace;
+    let separator = if namespace.ends_with(delimiter) {
+        ""
+    } else {
+        delimiter
+    };
+    format!(
+        "{}{}{}{}{}",
+        namespace, separator, context.port_type, delimiter, context.input
+    )
+}
+
+/// WS-Addressing interceptor.
+#[derive(Clone, Debug, Default)]
+pub struct Addressing {
+    /// Endpoint replies are sent to, `ANONYMOUS` if `None`.
+    pub reply_to: Option<String>,
+    /// Requests are registered in the correlator, and removed when a reply
+    /// relating to them is received in the HTTP response.
+    pub correlator: Option<Correlator>,
+}
+
+impl Addressing {
+    pub fn new() -> Self {
+        Addressing::default()
+    }
+
+    pub fn with_reply_to<S: Into<String>>(mut self, address: S) -> Self {
+        self.reply_to = Some(address.into());
+        self
+    }
+
+    pub fn with_correlator(mut self, correlator: Correlator) -> Self {
+        self.correlator = Some(correlator);
+        self
+    }
+
+    /// Headers for a request. The action is the `soapAction` of the
+    /// operation, or the default action of the WSDL binding if there is none.
+    pub fn headers(&self, context: &RequestContext) -> Headers {
+        let action = match context.soap_action {
+            Some(action) => action.to_string(),
+            None => default_action(context),
+        };
+
+        Headers {
+            action: Some(action),
+            to: Some(context.url.to_string()),
+            message_id: Some(message_id()),
+            reply_to: Some(
+                self.reply_to
+                    .clone()
+                    .unwrap_or_else(|| ANONYMOUS.to_string()),
+            ),
+            relates_to: None,
+        }
+    }
+}
+
+impl Interceptor for Addressing {
+    fn before_send(
+        &self,
+        context: &mut RequestContext,
+        envelope: &mut Element,
+    ) -> Result<(), crate::Error> {
+        let headers = self.headers(context);
+        if let (Some(correlator), Some(message_id)) =
+            (self.correlator.as_ref(), headers.message_id.as_ref())
+        {
+            correlator.register(message_id.as_str(), context.operation);
+        }
+
+        if envelope.get_child("soap:Header").is_none() {
+            envelope
+                .children
+                .insert(0, XMLNode::Element(Element::node("soap:Header")));
+        }
+        let header = envelope.get_mut_child("soap:Header").unwrap();
+        header
+            .children
+            .extend(headers.to_elements().into_iter().map(XMLNode::Element));
+        Ok(())
+    }
+
+    fn after_receive(
+        &self,
+        _context: &RequestContext,
+        response: &HttpResponse,
+    ) -> Result<(), crate::Error> {
+        if let Some(correlator) = self.correlator.as_ref() {
+            // errors are reported when the response is parsed
+            if let Ok(headers) = Headers::from_xml(&String::from_utf8_lossy(&response.body)) {
+                correlator.correlate(&headers);
+            }
+        }
+        Ok(())
+    }
+
+    fn on_error(&self, _context: &RequestContext, envelope: &Element, _error: &crate::Error) {
+        let message_id = envelope
+            .get_child("soap:Header")
+            .and_then(|h| h.get_child("wsa:MessageID"))
+            .and_then(|m| m.get_text());
+        if let (Some(correlator), Some(message_id)) = (self.correlator.as_ref(), message_id) {
+            correlator.forget(&message_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpser::{Method, Response};
+
+    fn context() -> RequestContext<'static> {
+        RequestContext {
+            operation: "Echo",
+            namespace: "http://example.com/test/",
+            port_type: "EchoPortType",
+            input: "EchoRequest",
+            soap_action: None,
+            url: "http://localhost/echo",
+            headers: vec![],
+            started: Instant::now(),
+        }
+    }
+
+    fn reply(relates_to: &str) -> String {
+        let mut envelope = Method::new("EchoResponse").as_element("urn:test");
+        let header = envelope.get_mut_child("soap:Header").unwrap();
+        for element in (Headers {
+            action: Some("urn:test/EchoResponse".to_string()),
+            relates_to: Some(relates_to.to_string()),
+            ..Headers::default()
+        })
+        .to_elements()
+        {
+            header.children.push(XMLNode::Element(element));
+        }
+        envelope.to_string()
+    }
+
+    #[test]
+    fn message_id_format() {
+        let id = message_id();
+        assert_eq!(id.len(), 45);
+        assert!(id.starts_with("urn:uuid:"));
+        assert_eq!(&id[23..24], "4");
+        assert_ne!(id, message_id());
+    }
+
+    #[test]
+    fn adds_headers() {
+        let mut envelope = Method::new("Echo").as_element("urn:test");
+        Addressing::new()
+            .before_send(&mut context(), &mut envelope)
+            .unwrap();
+
+        let response = Response::from_xml(&envelope.to_string()).unwrap();
+        let headers = Headers::from_header(&response.header.unwrap());
+        assert_eq!(
+            headers.action.as_deref(),
+            Some("http://example.com/test/EchoPortType/EchoRequest")
+        );
+        assert_eq!(headers.to.as_deref(), Some("http://localhost/echo"));
+        assert_eq!(headers.reply_to.as_deref(), Some(ANONYMOUS));
+        assert!(headers.message_id.unwrap().starts_with("urn:uuid:"));
+        assert_eq!(headers.relates_to, None);
+    }
+
+    #[test]
+    fn default_actions() {
+        let mut context = context();
+        context.namespace = "http://example.com/test";
+        assert_eq!(
+            default_action(&context),
+            "http://example.com/test/EchoPortType/EchoRequest"
+        );
+        context.namespace = "urn:example:test";
+        context.input = "Echo";
+        assert_eq!(
+            default_action(&context),
+            "urn:example:test:EchoPortType:Echo"
+        );
+    }
+
+    #[test]
+    fn correlates_replies() {
+        let correlator = Correlator::new();
+        let addressing = Addressing::new()
+            .with_reply_to("http://localhost/replies")
+            .with_correlator(correlator.clone());
+
+        let mut context = context();
+        context.soap_action = Some("urn:test/EchoAction");
+        let mut envelope = Method::new("Echo").as_element("urn:test");
+        addressing.before_send(&mut context, &mut envelope).unwrap();
+
+        let request = Headers::from_xml(&envelope.to_string()).unwrap();
+        assert_eq!(request.action.as_deref(), Some("urn:test/EchoAction"));
+        assert_eq!(
+            request.reply_to.as_deref(),
+            Some("http://localhost/replies")
+        );
+        assert_eq!(correlator.len(), 1);
+
+        assert!(correlator
+            .correlate(&Headers::from_xml(&reply("urn:uuid:unknown")).unwrap())
+            .is_none());
+
+        let reply = Headers::from_xml(&reply(request.message_id.as_ref().unwrap())).unwrap();
+        let pending = correlator.correlate(&reply).unwrap();
+        assert_eq!(pending.operation, "Echo");
+        assert_eq!(Some(pending.message_id), request.message_id);
+        assert!(correlator.is_empty());
+    }
+
+    #[test]
+    fn forgets_failed_and_expired_requests() {
+        let correlator = Correlator::new();
+        let addressing = Addressing::new()
+            .with_reply_to("http://localhost/replies")
+            .with_correlator(correlator.clone());
+
+        let mut envelope = Method::new("Echo").as_element("urn:test");
+        addressing
+            .before_send(&mut context(), &mut envelope)
+            .unwrap();
+        assert_eq!(correlator.len(), 1);
+        addressing.on_error(
+            &context(),
+            &envelope,
+            &crate::Error::Transport("timed out".into()),
+        );
+        assert!(correlator.is_empty());
+
+        let correlator = correlator.with_expiry(Duration::from_millis(0));
+        correlator.register("urn:uuid:first", "Echo");
+        correlator.register("urn:uuid:second", "Echo");
+        assert_eq!(correlator.len(), 1);
+        assert!(correlator.forget("urn:uuid:second").is_some());
+        assert!(correlator.is_empty());
+    }
+}
@@ -2,8 +2,22 @@
 pub enum Error {
     Wsdl(crate::wsdl::WsdlError),
     Reqwest(reqwest::Error),
+    Transport(Box<dyn std::error::Error + Send + Sync>),
     Rpser(crate::rpser::xml::Error),
+    Rpc(crate::rpser::RpcError),
     Num(std::num::ParseFloatError),
+    Mime(crate::mime::Error),
+    Cassette(crate::testing::cassette::Error),
+    #[cfg(feature = "signature")]
+    Signature(crate::security::signature::SignatureError),
+    /// The HTTP response does not contain a SOAP envelope.
+    ///
+    /// `body` holds the beginning of the response body, for diagnostics.
+    UnexpectedResponse {
+        status: u16,
+        content_type: Option<String>,
+        body: String,
+    },
 }
 
 impl From<crate::wsdl::WsdlError> for Error {
@@ -18,14 +32,39 @@ impl From<crate::rpser::xml::Error> for Error {
     }
 }
 
+impl From<crate::rpser::RpcError> for Error {
+    fn from(e: crate::rpser::RpcError) -> Self {
+        Error::Rpc(e)
+    }
+}
+
 impl From<reqwest::Error> for Error {
     fn from(e: reqwest::Error) -> Self {
         Error::Reqwest(e)
     }
 }
 
+impl From<crate::mime::Error> for Error {
+    fn from(e: crate::mime::Error) -> Self {
+        Error::Mime(e)
+    }
+}
+
+impl From<crate::testing::cassette::Error> for Error {
+    fn from(e: crate::testing::cassette::Error) -> Self {
+        Error::Cassette(e)
+    }
+}
+
 impl From<std::num::ParseFloatError> for Error {
     fn from(e: std::num::ParseFloatError) -> Self {
         Error::Num(e)
     }
 }
+
+#[cfg(feature = "signature")]
+impl From<crate::security::signature::SignatureError> for Error {
+    fn from(e: crate::security::signature::SignatureError) -> Self {
+        Error::Signature(e)
+    }
+}
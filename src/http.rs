@@ -1,69 +1,982 @@
 use crate::gen::{FromElement, ToElements};
+use crate::interceptor::{Chain, RequestContext};
+use crate::mime::{self, Attachment};
+use crate::mtom;
+use crate::retry::RetryPolicy;
+use crate::rpser::xml::BuildElement;
 use crate::rpser::{Method, Response};
-use reqwest::Client;
+use crate::transport::{HttpRequest, HttpResponse, Transport};
+use std::borrow::Cow;
 use std::fmt::Debug;
+use std::time::{Duration, Instant};
+use xmltree::{Element, XMLNode};
 
-pub async fn one_way<Input: ToElements>(
-    client: &Client,
-    base_url: &str,
-    ns: &str,
-    method: &str,
+/// Description of a call made by a generated client.
+#[derive(Clone, Copy, Debug)]
+pub struct Call<'a> {
+    pub base_url: &'a str,
+    pub namespace: &'a str,
+    /// Name of the WSDL port type.
+    pub port_type: &'a str,
+    pub operation: &'a str,
+    /// Name of the operation's input in the port type.
+    pub input: &'a str,
+    /// `soapAction` from the WSDL binding, if any.
+    pub soap_action: Option<&'a str>,
+    /// Send binary content with MTOM.
+    pub mtom: bool,
+    /// Retry policy, `None` if the operation is not idempotent.
+    pub retry: Option<&'a RetryPolicy>,
+    pub interceptors: &'a Chain,
+    pub options: &'a CallOptions,
+}
+
+/// Options of a single call, overriding the client defaults.
+#[derive(Clone, Debug, Default)]
+pub struct CallOptions {
+    /// Timeout of each attempt.
+    pub timeout: Option<Duration>,
+    /// URL used instead of the client's `base_url`.
+    pub endpoint: Option<String>,
+    /// HTTP headers, replacing the default headers with the same name.
+    pub headers: Vec<(String, String)>,
+    /// Elements added to the `soap:Header` of the envelope.
+    pub soap_headers: Vec<Element>,
+}
+
+impl CallOptions {
+    pub fn new() -> Self {
+        CallOptions::default()
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_endpoint<S: Into<String>>(mut self, url: S) -> Self {
+        self.endpoint = Some(url.into());
+        self
+    }
+
+    pub fn with_header<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.headers.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn with_soap_header(mut self, element: Element) -> Self {
+        self.soap_headers.push(element);
+        self
+    }
+}
+
+/// Output message of an operation, with the attachments of the response.
+#[derive(Clone, Debug)]
+pub struct Reply<Output> {
+    pub body: Output,
+    pub attachments: Vec<Attachment>,
+}
+
+pub async fn one_way<T: Transport, Input: ToElements>(
+    transport: &T,
+    call: &Call<'_>,
     input: &Input,
 ) -> Result<(), crate::Error> {
-    let mut v = input.to_elements();
-    let mut m = Method::new(method);
+    one_way_with_attachments(transport, call, input, vec![]).await
+}
 
-    for el in v.drain(..) {
-        m = m.with(el);
+pub async fn one_way_with_attachments<T: Transport, Input: ToElements>(
+    transport: &T,
+    call: &Call<'_>,
+    input: &Input,
+    attachments: Vec<Attachment>,
+) -> Result<(), crate::Error> {
+    let mut attachments = attachments;
+    let mut attempt = 1;
+    loop {
+        // each attempt is a new message for the interceptors
+        let (context, envelope, request) =
+            prepare(call, input, attempt_attachments(call, &mut attachments))?;
+        let result = match transport.send(request).await {
+            Ok(response) => call
+                .interceptors
+                .after_receive(&context, &response)
+                .and_then(|_| check_one_way_response(&response)),
+            Err(e) => Err(e),
+        };
+        let result = report_error(call, &context, &envelope, result);
+        match retry_delay(call, attempt, result) {
+            Ok(result) => return result,
+            Err(delay) => tokio::time::delay_for(delay).await,
+        }
+        attempt += 1;
     }
-    let s = m.as_xml(ns);
-    trace!("sending: {}", s);
-
-    let response: String = client
-        .post(base_url)
-        .header("Content-Type", "text/xml")
-        .header("MessageType", "Call")
-        .body(s)
-        .send()
-        .await?
-        .text()
-        .await?;
-
-    trace!("received: {}", response);
-    Ok(())
 }
 
-pub async fn request_response<Input: ToElements, Output: Debug + FromElement, Error>(
-    client: &Client,
-    base_url: &str,
-    ns: &str,
-    method: &str,
+pub async fn request_response<
+    T: Transport,
+    Input: ToElements,
+    Output: Debug + FromElement,
+    Error,
+>(
+    transport: &T,
+    call: &Call<'_>,
     input: &Input,
 ) -> Result<Result<Output, Error>, crate::Error> {
-    let mut v = input.to_elements();
-    let mut m = Method::new(method);
+    let reply = request_response_with_attachments(transport, call, input, vec![]).await?;
+    Ok(reply.map(|r| r.body))
+}
+
+/// Send a request with SOAP attachments, and return the output message
+/// with the attachments of the response.
+pub async fn request_response_with_attachments<
+    T: Transport,
+    Input: ToElements,
+    Output: Debug + FromElement,
+    Error,
+>(
+    transport: &T,
+    call: &Call<'_>,
+    input: &Input,
+    attachments: Vec<Attachment>,
+) -> Result<Result<Reply<Output>, Error>, crate::Error> {
+    let mut attachments = attachments;
+    let mut attempt = 1;
+    loop {
+        // each attempt is a new message for the interceptors
+        let (context, envelope, request) =
+            prepare(call, input, attempt_attachments(call, &mut attachments))?;
+        let result = match transport.send(request).await {
+            Ok(response) => call
+                .interceptors
+                .after_receive(&context, &response)
+                .and_then(|_| parse_response_with_attachments(&response)),
+            Err(e) => Err(e),
+        };
+        let result = report_error(call, &context, &envelope, result);
+        match retry_delay(call, attempt, result) {
+            Ok(result) => return result,
+            Err(delay) => tokio::time::delay_for(delay).await,
+        }
+        attempt += 1;
+    }
+}
+
+/// Return the result of an attempt, or the delay before the next one if
+/// it failed and the call can be retried.
+fn retry_delay<T>(
+    call: &Call<'_>,
+    attempt: u32,
+    result: Result<T, crate::Error>,
+) -> Result<Result<T, crate::Error>, Duration> {
+    let error = match result {
+        Err(error) => error,
+        ok => return Ok(ok),
+    };
+
+    match call.retry.and_then(|p| p.next_delay(attempt, &error)) {
+        Some(delay) => {
+            warn!(
+                "{} failed (attempt {}), retrying in {:?}: {:?}",
+                call.operation, attempt, delay, error
+            );
+            Err(delay)
+        }
+        None => Ok(Err(error)),
+    }
+}
+
+/// Attachments of an attempt. They are only copied when the call may be
+/// retried.
+fn attempt_attachments(call: &Call<'_>, attachments: &mut Vec<Attachment>) -> Vec<Attachment> {
+    if call.retry.is_some() {
+        attachments.clone()
+    } else {
+        std::mem::take(attachments)
+    }
+}
+
+/// Pass the error of a failed attempt to the interceptors' `on_error` hooks.
+fn report_error<T>(
+    call: &Call<'_>,
+    context: &RequestContext,
+    envelope: &Element,
+    result: Result<T, crate::Error>,
+) -> Result<T, crate::Error> {
+    if let Err(error) = result.as_ref() {
+        call.interceptors.on_error(context, envelope, error);
+    }
+    result
+}
+
+/// Serialize the input message to an envelope and wrap it in an HTTP request.
+///
+/// The interceptors' `before_send` hooks are applied to the envelope. The
+/// returned context must be passed to their `after_receive` hooks.
+///
+/// With MTOM, binary content is moved to separate parts before the
+/// interceptors are called.
+pub fn build_request<'a, Input: ToElements>(
+    call: &Call<'a>,
+    input: &Input,
+) -> Result<(RequestContext<'a>, HttpRequest), crate::Error> {
+    build_request_with_attachments(call, input, vec![])
+}
+
+/// Like `build_request`, sending the request as `multipart/related` if
+/// there are attachments.
+pub fn build_request_with_attachments<'a, Input: ToElements>(
+    call: &Call<'a>,
+    input: &Input,
+    attachments: Vec<Attachment>,
+) -> Result<(RequestContext<'a>, HttpRequest), crate::Error> {
+    prepare(call, input, attachments).map(|(context, _, request)| (context, request))
+}
+
+/// Build the request, also returning the envelope passed to the
+/// interceptors.
+fn prepare<'a, Input: ToElements>(
+    call: &Call<'a>,
+    input: &Input,
+    attachments: Vec<Attachment>,
+) -> Result<(RequestContext<'a>, Element, HttpRequest), crate::Error> {
+    let (mut v, mut binaries) = if call.mtom {
+        mtom::collect(|| input.to_elements())
+    } else {
+        (input.to_elements(), mtom::Binaries::new())
+    };
+    let mut m = Method::new(call.operation);
 
     for el in v.drain(..) {
         m = m.with(el);
     }
-    let s = m.as_xml(ns);
+    let mut envelope = m.as_element(call.namespace);
+    if !call.options.soap_headers.is_empty() {
+        if envelope.get_child("soap:Header").is_none() {
+            envelope
+                .children
+                .insert(0, XMLNode::Element(Element::node("soap:Header")));
+        }
+        let header = envelope.get_mut_child("soap:Header").unwrap();
+        header.children.extend(
+            call.options
+                .soap_headers
+                .iter()
+                .cloned()
+                .map(XMLNode::Element),
+        );
+    }
+    let parts = if call.mtom {
+        mtom::extract(&mut envelope, &mut binaries)
+    } else {
+        mtom::strip_markers(&mut envelope);
+        vec![]
+    };
+
+    let mut context = RequestContext {
+        operation: call.operation,
+        namespace: call.namespace,
+        port_type: call.port_type,
+        input: call.input,
+        soap_action: call.soap_action,
+        url: call.options.endpoint.as_deref().unwrap_or(call.base_url),
+        headers: vec![
+            ("Content-Type".to_string(), "text/xml".to_string()),
+            ("MessageType".to_string(), "Call".to_string()),
+            (
+                "SOAPAction".to_string(),
+                format!("\"{}\"", call.soap_action.unwrap_or_default()),
+            ),
+        ],
+        started: Instant::now(),
+    };
+    for (key, value) in call.options.headers.iter() {
+        context.set_header(key, value.as_str());
+    }
+    call.interceptors.before_send(&mut context, &mut envelope)?;
+
+    let s = envelope.to_string();
     trace!("sending: {}", s);
 
-    let response: String = client
-        .post(base_url)
-        .header("Content-Type", "text/xml")
-        .header("MessageType", "Call")
-        .body(s)
-        .send()
-        .await?
-        .text()
-        .await?;
-
-    trace!("received: {}", response);
-    let r = Response::from_xml(&response).unwrap();
+    let body = if call.mtom {
+        let mut parts = parts;
+        parts.extend(attachments.into_iter().map(mime::Part::from));
+        let (content_type, body) = mtom::encode(s, parts);
+        context.set_header("Content-Type", content_type);
+        body
+    } else if !attachments.is_empty() {
+        let (content_type, body) = mime::related(s.into_bytes(), attachments);
+        context.set_header("Content-Type", content_type);
+        body
+    } else {
+        s.into_bytes()
+    };
+
+    let request = HttpRequest {
+        url: context.url.to_string(),
+        headers: context.headers.clone(),
+        body,
+        timeout: call.options.timeout,
+    };
+    Ok((context, envelope, request))
+}
+
+/// Maximum length of the body kept in `Error::UnexpectedResponse`.
+const BODY_SNIPPET_LEN: usize = 512;
+
+fn unexpected_response(response: &HttpResponse) -> crate::Error {
+    let body = String::from_utf8_lossy(&response.body);
+    let body = match body.char_indices().nth(BODY_SNIPPET_LEN) {
+        Some((index, _)) => format!("{}...", &body[..index]),
+        None => body.into_owned(),
+    };
+
+    crate::Error::UnexpectedResponse {
+        status: response.status,
+        content_type: response.header("Content-Type").map(|s| s.to_string()),
+        body,
+    }
+}
+
+fn is_xml_content_type(content_type: &str) -> bool {
+    let media_type = mime::media_type(content_type);
+    media_type.ends_with("/xml") || media_type.ends_with("+xml")
+}
+
+/// Replace a `multipart/related` response by its root envelope. The MTOM
+/// binary parts are returned in a side table, and the other parts as
+/// attachments.
+fn decode_multipart(
+    response: &HttpResponse,
+) -> Result<(Cow<'_, HttpResponse>, mtom::Binaries, Vec<Attachment>), crate::Error> {
+    match response.header("Content-Type") {
+        Some(content_type) if mime::media_type(content_type) == "multipart/related" => {
+            let (body, binaries, parts) = mtom::decode(content_type, &response.body)?;
+            let headers = response
+                .headers
+                .iter()
+                .filter(|(k, _)| !k.eq_ignore_ascii_case("Content-Type"))
+                .cloned()
+                .chain(std::iter::once((
+                    "Content-Type".to_string(),
+                    "text/xml".to_string(),
+                )))
+                .collect();
+
+            Ok((
+                Cow::Owned(HttpResponse {
+                    status: response.status,
+                    headers,
+                    body,
+                }),
+                binaries,
+                parts.into_iter().map(Attachment::from).collect(),
+            ))
+        }
+        _ => Ok((Cow::Borrowed(response), mtom::Binaries::new(), vec![])),
+    }
+}
+
+/// Check that the response can contain a SOAP envelope.
+///
+/// Only 2xx and 500 (used for SOAP faults) responses with a non empty body
+/// and an XML content type (or no content type at all) are accepted.
+fn check_envelope_response(response: &HttpResponse) -> Result<(), crate::Error> {
+    let status_ok = (200..300).contains(&response.status) || response.status == 500;
+    let content_type_ok = response
+        .header("Content-Type")
+        .map(is_xml_content_type)
+        .unwrap_or(true);
+    let body_ok = response.body.iter().any(|b| !b.is_ascii_whitespace());
+
+    if status_ok && content_type_ok && body_ok {
+        Ok(())
+    } else {
+        Err(unexpected_response(response))
+    }
+}
+
+/// Check the response to a one way operation.
+///
+/// An empty 2xx response is expected, but faults are still reported.
+pub fn check_one_way_response(response: &HttpResponse) -> Result<(), crate::Error> {
+    let (response, _, _) = decode_multipart(response)?;
+    let response = &*response;
+    trace!("received: {}", String::from_utf8_lossy(&response.body));
+    let empty = response.body.iter().all(|b| b.is_ascii_whitespace());
+
+    if (200..300).contains(&response.status) && empty {
+        return Ok(());
+    }
+
+    check_envelope_response(response)?;
+    let response_text = String::from_utf8_lossy(&response.body);
+    Response::from_xml(&response_text)?;
+
+    if response.status == 500 {
+        return Err(unexpected_response(response));
+    }
+    Ok(())
+}
+
+/// Parse the response envelope and deserialize the output message.
+///
+/// SOAP faults are returned as `Error::Rpc(RpcError::Fault { .. })`.
+pub fn parse_response<Output: Debug + FromElement, Error>(
+    response: &HttpResponse,
+) -> Result<Result<Output, Error>, crate::Error> {
+    let reply = parse_response_with_attachments(response)?;
+    Ok(reply.map(|r| r.body))
+}
+
+/// Like `parse_response`, also returning the attachments of
+/// `multipart/related` responses.
+pub fn parse_response_with_attachments<Output: Debug + FromElement, Error>(
+    response: &HttpResponse,
+) -> Result<Result<Reply<Output>, Error>, crate::Error> {
+    let (response, binaries, attachments) = decode_multipart(response)?;
+    let response = &*response;
+    check_envelope_response(response)?;
+
+    let response_text = String::from_utf8_lossy(&response.body);
+    trace!("received: {}", response_text);
+    let r = Response::from_xml(&response_text)?;
     trace!("parsed: {:#?}", r);
-    let o = Output::from_element(&r.body);
+
+    // a 500 response should contain a fault, which was returned above
+    if response.status == 500 {
+        return Err(unexpected_response(response));
+    }
+
+    let o = mtom::with_binaries(binaries, || Output::from_element(&r.body));
     trace!("output: {:#?}", o);
 
-    o.map(Ok)
+    o.map(|body| Ok(Reply { body, attachments }))
+}
+
+/// Synchronous versions of the calls, used by the blocking clients.
+#[cfg(feature = "blocking")]
+pub mod blocking {
+    use super::{
+        attempt_attachments, check_one_way_response, parse_response_with_attachments, prepare,
+        report_error, retry_delay, Call, Reply,
+    };
+    use crate::gen::{FromElement, ToElements};
+    use crate::mime::Attachment;
+    use crate::transport::blocking::Transport;
+    use std::fmt::Debug;
+
+    pub fn one_way<T: Transport, Input: ToElements>(
+        transport: &T,
+        call: &Call<'_>,
+        input: &Input,
+    ) -> Result<(), crate::Error> {
+        one_way_with_attachments(transport, call, input, vec![])
+    }
+
+    pub fn one_way_with_attachments<T: Transport, Input: ToElements>(
+        transport: &T,
+        call: &Call<'_>,
+        input: &Input,
+        attachments: Vec<Attachment>,
+    ) -> Result<(), crate::Error> {
+        let mut attachments = attachments;
+        let mut attempt = 1;
+        loop {
+            let (context, envelope, request) =
+                prepare(call, input, attempt_attachments(call, &mut attachments))?;
+            let result = transport.send(request).and_then(|response| {
+                call.interceptors.after_receive(&context, &response)?;
+                check_one_way_response(&response)
+            });
+            let result = report_error(call, &context, &envelope, result);
+            match retry_delay(call, attempt, result) {
+                Ok(result) => return result,
+                Err(delay) => std::thread::sleep(delay),
+            }
+            attempt += 1;
+        }
+    }
+
+    pub fn request_response<T: Transport, Input: ToElements, Output: Debug + FromElement, Error>(
+        transport: &T,
+        call: &Call<'_>,
+        input: &Input,
+    ) -> Result<Result<Output, Error>, crate::Error> {
+        let reply = request_response_with_attachments(transport, call, input, vec![])?;
+        Ok(reply.map(|r| r.body))
+    }
+
+    pub fn request_response_with_attachments<
+        T: Transport,
+        Input: ToElements,
+        Output: Debug + FromElement,
+        Error,
+    >(
+        transport: &T,
+        call: &Call<'_>,
+        input: &Input,
+        attachments: Vec<Attachment>,
+    ) -> Result<Result<Reply<Output>, Error>, crate::Error> {
+        let mut attachments = attachments;
+        let mut attempt = 1;
+        loop {
+            let (context, envelope, request) =
+                prepare(call, input, attempt_attachments(call, &mut attachments))?;
+            let result = transport.send(request).and_then(|response| {
+                call.interceptors.after_receive(&context, &response)?;
+                parse_response_with_attachments(&response)
+            });
+            let result = report_error(call, &context, &envelope, result);
+            match retry_delay(call, attempt, result) {
+                Ok(result) => return result,
+                Err(delay) => std::thread::sleep(delay),
+            }
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpser::xml::BuildElement;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+    use xmltree::Element;
+
+    #[derive(Debug, Default)]
+    struct Echo(String);
+
+    impl ToElements for Echo {
+        fn to_elements(&self) -> Vec<Element> {
+            vec![Element::node("value").with_text(self.0.clone())]
+        }
+    }
+
+    impl FromElement for Echo {
+        fn from_element(element: &Element) -> Result<Self, crate::Error> {
+            let value = element.get_at_path(&["value"])?;
+            Ok(Echo(value.get_text().unwrap_or_default().to_string()))
+        }
+    }
+
+    #[derive(Default)]
+    struct MockTransport {
+        requests: Mutex<Vec<HttpRequest>>,
+    }
+
+    #[async_trait]
+    impl Transport for MockTransport {
+        async fn send(&self, request: HttpRequest) -> Result<HttpResponse, crate::Error> {
+            self.requests.lock().unwrap().push(request);
+            Ok(HttpResponse {
+                status: 200,
+                headers: vec![("Content-Type".to_string(), "text/xml".to_string())],
+                body: br#"<?xml version="1.0" encoding="utf-8"?>
+                    <soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/">
+                        <soap:Body>
+                            <ns:EchoResponse xmlns:ns="urn:test"><value>pong</value></ns:EchoResponse>
+                        </soap:Body>
+                    </soap:Envelope>"#
+                    .to_vec(),
+            })
+        }
+    }
+
+    fn response(status: u16, content_type: &str, body: &str) -> HttpResponse {
+        HttpResponse {
+            status,
+            headers: vec![("Content-Type".to_string(), content_type.to_string())],
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn rejects_non_xml_responses() {
+        let html = response(502, "text/html", "<html><body>Bad Gateway</body></html>");
+        match parse_response::<Echo, ()>(&html) {
+            Err(crate::Error::UnexpectedResponse {
+                status,
+                content_type,
+                body,
+            }) => {
+                assert_eq!(status, 502);
+                assert_eq!(content_type.as_deref(), Some("text/html"));
+                assert_eq!(body, "<html><body>Bad Gateway</body></html>");
+            }
+            other => panic!("expected an unexpected response error, got {:?}", other),
+        }
+
+        let empty = response(200, "text/xml", "");
+        match parse_response::<Echo, ()>(&empty) {
+            Err(crate::Error::UnexpectedResponse { status: 200, .. }) => {}
+            other => panic!("expected an unexpected response error, got {:?}", other),
+        }
+
+        let html = response(200, "text/html; charset=utf-8", "<html></html>");
+        match parse_response::<Echo, ()>(&html) {
+            Err(crate::Error::UnexpectedResponse { status: 200, .. }) => {}
+            other => panic!("expected an unexpected response error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn truncates_body_snippet() {
+        let long = response(503, "text/plain", &"a".repeat(10_000));
+        match parse_response::<Echo, ()>(&long) {
+            Err(crate::Error::UnexpectedResponse { status, body, .. }) => {
+                assert_eq!(status, 503);
+                assert_eq!(body.len(), BODY_SNIPPET_LEN + 3);
+            }
+            other => panic!("expected an unexpected response error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn server_error_is_a_fault() {
+        let fault = response(
+            500,
+            "text/xml; charset=utf-8",
+            r#"<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/">
+                <soap:Body>
+                    <soap:Fault>
+                        <faultcode>soap:Server</faultcode>
+                        <faultstring>Internal error</faultstring>
+                    </soap:Fault>
+                </soap:Body>
+            </soap:Envelope>"#,
+        );
+
+        match parse_response::<Echo, ()>(&fault) {
+            Err(crate::Error::Rpc(crate::rpser::RpcError::Fault {
+                fault_code,
+                fault_string,
+                ..
+            })) => {
+                assert_eq!(fault_code, "soap:Server");
+                assert_eq!(fault_string, "Internal error");
+            }
+            other => panic!("expected a fault, got {:?}", other),
+        }
+
+        match check_one_way_response(&fault) {
+            Err(crate::Error::Rpc(crate::rpser::RpcError::Fault { .. })) => {}
+            other => panic!("expected a fault, got {:?}", other),
+        }
+        assert!(check_one_way_response(&response(202, "text/xml", "")).is_ok());
+    }
+
+    #[tokio::test]
+    async fn request_response_with_custom_transport() {
+        let transport = MockTransport::default();
+        let interceptors = Chain::new();
+        let call = Call {
+            base_url: "http://localhost/echo",
+            namespace: "urn:test",
+            port_type: "TestPortType",
+            operation: "Echo",
+            input: "EchoRequest",
+            soap_action: Some("urn:test/Echo"),
+            mtom: false,
+            retry: None,
+            options: &CallOptions::default(),
+            interceptors: &interceptors,
+        };
+        let res: Result<Echo, ()> = request_response(&transport, &call, &Echo("ping".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(res.unwrap().0, "pong");
+
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].url, "http://localhost/echo");
+        assert_eq!(requests[0].header("content-type"), Some("text/xml"));
+        assert_eq!(requests[0].header("SOAPAction"), Some("\"urn:test/Echo\""));
+        let body = String::from_utf8_lossy(&requests[0].body);
+        assert!(body.contains("<ns:Echo><value>ping</value></ns:Echo>"));
+    }
+
+    #[tokio::test]
+    async fn call_options() {
+        let transport = MockTransport::default();
+        let interceptors = Chain::new();
+        let options = CallOptions::new()
+            .with_timeout(Duration::from_secs(120))
+            .with_endpoint("http://backup/echo")
+            .with_header("X-Batch", "nightly")
+            .with_header("MessageType", "Batch")
+            .with_soap_header(Element::node("Tenant").with_text("acme"));
+        let call = Call {
+            base_url: "http://localhost/echo",
+            namespace: "urn:test",
+            port_type: "TestPortType",
+            operation: "Echo",
+            input: "EchoRequest",
+            soap_action: None,
+            mtom: false,
+            retry: None,
+            options: &options,
+            interceptors: &interceptors,
+        };
+        let res: Result<Echo, ()> = request_response(&transport, &call, &Echo("ping".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(res.unwrap().0, "pong");
+
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests[0].url, "http://backup/echo");
+        assert_eq!(requests[0].timeout, Some(Duration::from_secs(120)));
+        assert_eq!(requests[0].header("X-Batch"), Some("nightly"));
+        assert_eq!(requests[0].header("MessageType"), Some("Batch"));
+        let body = String::from_utf8_lossy(&requests[0].body);
+        assert!(body.contains("<Tenant>acme</Tenant></soap:Header>"));
+    }
+
+    struct CorrelationId;
+
+    impl crate::interceptor::Interceptor for CorrelationId {
+        fn before_send(
+            &self,
+            context: &mut RequestContext,
+            envelope: &mut Element,
+        ) -> Result<(), crate::Error> {
+            context.set_header("X-Correlation-Id", "42");
+            let header = envelope.get_mut_child("soap:Header").unwrap();
+            header.children.push(xmltree::XMLNode::Element(
+                Element::node("CorrelationId").with_text("42"),
+            ));
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        statuses: Mutex<Vec<(String, u16)>>,
+    }
+
+    impl crate::interceptor::Interceptor for std::sync::Arc<Recorder> {
+        fn after_receive(
+            &self,
+            context: &RequestContext,
+            response: &HttpResponse,
+        ) -> Result<(), crate::Error> {
+            let id = context.header("x-correlation-id").unwrap_or_default();
+            self.statuses
+                .lock()
+                .unwrap()
+                .push((id.to_string(), response.status));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn interceptors_see_request_and_response() {
+        let transport = MockTransport::default();
+        let recorder = std::sync::Arc::new(Recorder::default());
+        let mut interceptors = Chain::new();
+        interceptors.push(recorder.clone());
+        interceptors.push(CorrelationId);
+
+        let call = Call {
+            base_url: "http://localhost/echo",
+            namespace: "urn:test",
+            port_type: "TestPortType",
+            operation: "Echo",
+            input: "EchoRequest",
+            soap_action: Some("urn:test/Echo"),
+            mtom: false,
+            retry: None,
+            options: &CallOptions::default(),
+            interceptors: &interceptors,
+        };
+        let res: Result<Echo, ()> = request_response(&transport, &call, &Echo("ping".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(res.unwrap().0, "pong");
+
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests[0].header("X-Correlation-Id"), Some("42"));
+        let body = String::from_utf8_lossy(&requests[0].body);
+        assert!(body.contains("<soap:Header><CorrelationId>42</CorrelationId></soap:Header>"));
+
+        assert_eq!(
+            *recorder.statuses.lock().unwrap(),
+            vec![("42".to_string(), 200)]
+        );
+    }
+
+    #[derive(Debug)]
+    struct Blob(Vec<u8>);
+
+    impl ToElements for Blob {
+        fn to_elements(&self) -> Vec<Element> {
+            vec![crate::mtom::binary_element("data", &self.0)]
+        }
+    }
+
+    impl FromElement for Blob {
+        fn from_element(element: &Element) -> Result<Self, crate::Error> {
+            Ok(Blob(element.get_at_path(&["data"])?.as_binary()?))
+        }
+    }
+
+    /// Sends the request back as the response.
+    struct Mirror;
+
+    #[async_trait]
+    impl Transport for Mirror {
+        async fn send(&self, request: HttpRequest) -> Result<HttpResponse, crate::Error> {
+            Ok(HttpResponse {
+                status: 200,
+                headers: request.headers,
+                body: request.body,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn mtom_request_and_response() {
+        let interceptors = Chain::new();
+        let mut call = Call {
+            base_url: "http://localhost/archive",
+            namespace: "urn:test",
+            port_type: "TestPortType",
+            operation: "Store",
+            input: "StoreRequest",
+            soap_action: None,
+            mtom: true,
+            retry: None,
+            options: &CallOptions::default(),
+            interceptors: &interceptors,
+        };
+        let data = vec![0, 1, 2, 255, b'\r', b'\n'];
+
+        let (_, request) = build_request(&call, &Blob(data.clone())).unwrap();
+        let content_type = request.header("Content-Type").unwrap();
+        assert!(content_type.starts_with("multipart/related"));
+        let body = String::from_utf8_lossy(&request.body);
+        assert!(body.contains("<xop:Include"));
+        assert!(!body.contains(&base64::encode(&data)));
+
+        let res: Result<Blob, ()> = request_response(&Mirror, &call, &Blob(data.clone()))
+            .await
+            .unwrap();
+        assert_eq!(res.unwrap().0, data);
+
+        call.mtom = false;
+        let (_, request) = build_request(&call, &Blob(data.clone())).unwrap();
+        assert_eq!(request.header("Content-Type"), Some("text/xml"));
+        let body = String::from_utf8_lossy(&request.body);
+        assert!(body.contains(&format!("<data>{}</data>", base64::encode(&data))));
+    }
+
+    #[tokio::test]
+    async fn attachments_round_trip() {
+        let interceptors = Chain::new();
+        let call = Call {
+            base_url: "http://localhost/company",
+            namespace: "urn:test",
+            port_type: "TestPortType",
+            operation: "Echo",
+            input: "EchoRequest",
+            soap_action: None,
+            mtom: false,
+            retry: None,
+            options: &CallOptions::default(),
+            interceptors: &interceptors,
+        };
+        let logo = Attachment::new("logo=1@example.com", "image/gif", b"GIF89a".to_vec());
+
+        let (_, request) =
+            build_request_with_attachments(&call, &Echo("ping".to_string()), vec![logo.clone()])
+                .unwrap();
+        let content_type = request.header("Content-Type").unwrap();
+        assert!(content_type.starts_with(r#"multipart/related; type="text/xml""#));
+
+        let reply: Reply<Echo> = request_response_with_attachments::<_, _, _, ()>(
+            &Mirror,
+            &call,
+            &Echo("ping".to_string()),
+            vec![logo.clone()],
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(reply.body.0, "ping");
+        assert_eq!(reply.attachments, vec![logo]);
+    }
+
+    /// Fails the first attempts with a connection error.
+    #[derive(Default)]
+    struct Flaky {
+        bodies: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl Transport for Flaky {
+        async fn send(&self, request: HttpRequest) -> Result<HttpResponse, crate::Error> {
+            let attempts = {
+                let mut bodies = self.bodies.lock().unwrap();
+                bodies.push(String::from_utf8_lossy(&request.body).into_owned());
+                bodies.len()
+            };
+            if attempts < 3 {
+                return Err(crate::Error::Transport("connection refused".into()));
+            }
+            MockTransport::default().send(request).await
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_rebuild_the_envelope() {
+        use crate::addressing::Addressing;
+        use crate::security::{Security, UsernameToken};
+
+        let transport = Flaky::default();
+        let mut interceptors = Chain::new();
+        interceptors
+            .push(Security::new().with_username_token(UsernameToken::digest("alice", "secret")));
+        interceptors.push(Addressing::new());
+        let policy = crate::retry::RetryPolicy::new(3)
+            .with_backoff(Duration::from_millis(1), Duration::from_millis(1));
+        let call = Call {
+            base_url: "http://localhost/echo",
+            namespace: "urn:test",
+            port_type: "TestPortType",
+            operation: "Echo",
+            input: "EchoRequest",
+            soap_action: None,
+            mtom: false,
+            retry: Some(&policy),
+            options: &CallOptions::default(),
+            interceptors: &interceptors,
+        };
+        let res: Result<Echo, ()> = request_response(&transport, &call, &Echo("ping".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(res.unwrap().0, "pong");
+
+        let bodies = transport.bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 3);
+        let header = |body: &str, path: &[&str]| {
+            let envelope = Element::parse(body.as_bytes()).unwrap();
+            envelope
+                .get_at_path(path)
+                .unwrap()
+                .get_text()
+                .unwrap()
+                .into_owned()
+        };
+        let nonces = bodies
+            .iter()
+            .map(|b| header(b, &["Header", "Security", "UsernameToken", "Nonce"]))
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(nonces.len(), 3);
+        let message_ids = bodies
+            .iter()
+            .map(|b| header(b, &["Header", "MessageID"]))
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(message_ids.len(), 3);
+    }
 }
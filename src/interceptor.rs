@@ -0,0 +1,147 @@
+//! Hooks around the HTTP exchange of generated clients.
+//!
+//! Interceptors are called after the envelope is built and before it is
+//! serialized, so they can modify the envelope and the HTTP headers (to add
+//! SOAP headers, correlation IDs, signatures...), and once the raw response
+//! is received, before it is parsed.
+
+use crate::transport::HttpResponse;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+use xmltree::Element;
+
+/// Information about the request being sent.
+#[derive(Clone, Debug)]
+pub struct RequestContext<'a> {
+    /// Name of the operation, as found in the WSDL.
+    pub operation: &'a str,
+    /// Target namespace of the operation.
+    pub namespace: &'a str,
+    /// Name of the WSDL port type of the operation.
+    pub port_type: &'a str,
+    /// Name of the operation's input in the port type.
+    pub input: &'a str,
+    /// `soapAction` of the operation, from the WSDL binding.
+    pub soap_action: Option<&'a str>,
+    /// Endpoint the request will be sent to.
+    pub url: &'a str,
+    /// HTTP headers of the request.
+    pub headers: Vec<(String, String)>,
+    /// Time at which the call started.
+    pub started: Instant,
+}
+
+impl<'a> RequestContext<'a> {
+    /// Get the first header value matching `name` (case insensitive).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Set a header, replacing any existing value.
+    pub fn set_header<K: Into<String>, V: Into<String>>(&mut self, key: K, value: V) {
+        let key = key.into();
+        self.headers.retain(|(k, _)| !k.eq_ignore_ascii_case(&key));
+        self.headers.push((key, value.into()));
+    }
+}
+
+/// Request and response hook.
+///
+/// All methods do nothing by default, so implementors only need to
+/// override the ones they are interested in. Returning an error aborts
+/// the call.
+pub trait Interceptor: Send + Sync {
+    /// Called before sending the request.
+    ///
+    /// `envelope` is the full `soap:Envelope` element.
+    fn before_send(
+        &self,
+        _context: &mut RequestContext,
+        _envelope: &mut Element,
+    ) -> Result<(), crate::Error> {
+        Ok(())
+    }
+
+    /// Called with the raw response, before it is parsed.
+    fn after_receive(
+        &self,
+        _context: &RequestContext,
+        _response: &HttpResponse,
+    ) -> Result<(), crate::Error> {
+        Ok(())
+    }
+
+    /// Called when the call failed, with the envelope that was sent.
+    fn on_error(&self, _context: &RequestContext, _envelope: &Element, _error: &crate::Error) {}
+}
+
+/// Ordered list of interceptors.
+///
+/// `before_send` hooks are called in insertion order, `after_receive` hooks
+/// in reverse order, so the first interceptor wraps all the others.
+#[derive(Clone, Default)]
+pub struct Chain {
+    interceptors: Vec<Arc<dyn Interceptor>>,
+}
+
+impl Chain {
+    pub fn new() -> Self {
+        Chain::default()
+    }
+
+    /// Add an interceptor at the end of the chain.
+    pub fn push<I: Interceptor + 'static>(&mut self, interceptor: I) {
+        self.interceptors.push(Arc::new(interceptor));
+    }
+
+    /// Add a shared interceptor at the end of the chain.
+    pub fn push_shared(&mut self, interceptor: Arc<dyn Interceptor>) {
+        self.interceptors.push(interceptor);
+    }
+
+    pub fn len(&self) -> usize {
+        self.interceptors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.interceptors.is_empty()
+    }
+
+    pub fn before_send(
+        &self,
+        context: &mut RequestContext,
+        envelope: &mut Element,
+    ) -> Result<(), crate::Error> {
+        for interceptor in self.interceptors.iter() {
+            interceptor.before_send(context, envelope)?;
+        }
+        Ok(())
+    }
+
+    pub fn after_receive(
+        &self,
+        context: &RequestContext,
+        response: &HttpResponse,
+    ) -> Result<(), crate::Error> {
+        for interceptor in self.interceptors.iter().rev() {
+            interceptor.after_receive(context, response)?;
+        }
+        Ok(())
+    }
+
+    pub fn on_error(&self, context: &RequestContext, envelope: &Element, error: &crate::Error) {
+        for interceptor in self.interceptors.iter().rev() {
+            interceptor.on_error(context, envelope, error);
+        }
+    }
+}
+
+impl fmt::Debug for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Chain({} interceptors)", self.interceptors.len())
+    }
+}
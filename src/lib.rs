@@ -1,22 +1,31 @@
 #[macro_use]
 extern crate log;
-extern crate proc_macro;
-extern crate proc_macro2;
-#[macro_use]
-extern crate quote;
 
 // reexport dependencies so they can be used from the generated code
 // without requiring more imports
 #[doc(hidden)]
 pub mod internal {
+    pub use async_trait;
     pub use chrono;
     pub use reqwest;
+    #[cfg(feature = "serde")]
+    pub use serde;
     pub use xmltree;
 }
 
+pub mod addressing;
 mod error;
 pub mod gen;
 pub mod http;
+pub mod interceptor;
+pub mod mime;
+pub mod mtom;
+pub mod retry;
 pub mod rpser;
-pub mod wsdl;
+pub mod security;
+pub mod server;
+pub mod testing;
+pub mod transport;
 pub use error::*;
+pub use savon_gen::wsdl;
+pub use savon_macros::wsdl;
@@ -0,0 +1,402 @@
+//! `multipart/related` encoding and decoding, used for MTOM and SOAP with
+//! attachments.
+//!
+//! See RFC 2387.
+
+/// Multipart parsing error.
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// The content type has no `boundary` parameter.
+    MissingBoundary,
+    /// A part is not terminated by a boundary, or has malformed headers.
+    MalformedPart,
+    /// The part designated by the `start` parameter was not found.
+    MissingRoot(String),
+    /// A part referenced by the root part was not found.
+    MissingPart(String),
+    /// A part uses the base64 transfer encoding, but is not valid base64.
+    Base64(base64::DecodeError),
+}
+
+/// Body part of a multipart message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Part {
+    /// Headers other than `Content-Type` and `Content-ID`.
+    pub headers: Vec<(String, String)>,
+    pub content_type: String,
+    /// Content ID, without the angle brackets.
+    pub content_id: Option<String>,
+    pub body: Vec<u8>,
+}
+
+impl Part {
+    pub fn new<S: Into<String>>(content_type: S, body: Vec<u8>) -> Self {
+        Part {
+            headers: vec![],
+            content_type: content_type.into(),
+            content_id: None,
+            body,
+        }
+    }
+
+    pub fn with_content_id<S: Into<String>>(mut self, content_id: S) -> Self {
+        self.content_id = Some(content_id.into());
+        self
+    }
+
+    pub fn with_header<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.headers.push((key.into(), value.into()));
+        self
+    }
+
+    /// Get the first header value matching `name` (case insensitive).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Attachment sent or received with SOAP with Attachments.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attachment {
+    /// Content ID, without the angle brackets.
+    pub content_id: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+impl Attachment {
+    pub fn new<I: Into<String>, T: Into<String>>(
+        content_id: I,
+        content_type: T,
+        data: Vec<u8>,
+    ) -> Self {
+        Attachment {
+            content_id: content_id.into(),
+            content_type: content_type.into(),
+            data,
+        }
+    }
+
+    /// URL referencing the attachment from the envelope.
+    pub fn href(&self) -> String {
+        format!("cid:{}", self.content_id)
+    }
+}
+
+impl From<Attachment> for Part {
+    fn from(attachment: Attachment) -> Self {
+        Part::new(attachment.content_type, attachment.data)
+            .with_content_id(attachment.content_id)
+            .with_header("Content-Transfer-Encoding", "binary")
+    }
+}
+
+impl From<Part> for Attachment {
+    fn from(part: Part) -> Self {
+        Attachment {
+            content_id: part.content_id.unwrap_or_default(),
+            content_type: part.content_type,
+            data: part.body,
+        }
+    }
+}
+
+/// Content ID of the root part of the messages sent.
+pub const ROOT_CONTENT_ID: &str = "root.message@savon";
+
+/// Build a SOAP with Attachments message from a serialized envelope.
+/// Returns the `Content-Type` header value and the body.
+pub fn related(envelope: Vec<u8>, attachments: Vec<Attachment>) -> (String, Vec<u8>) {
+    let root = Part::new("text/xml; charset=UTF-8", envelope)
+        .with_content_id(ROOT_CONTENT_ID)
+        .with_header("Content-Transfer-Encoding", "8bit");
+
+    let mut message = Multipart::new(vec![root]);
+    message
+        .parts
+        .extend(attachments.into_iter().map(Part::from));
+
+    (message.content_type("text/xml", &[]), message.to_bytes())
+}
+
+/// `multipart/related` message. The first part is the root part.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Multipart {
+    pub boundary: String,
+    pub parts: Vec<Part>,
+}
+
+/// Generate a random content ID or boundary.
+pub fn random_id() -> String {
+    let bytes: [u8; 12] = rand::random();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Get a parameter of a `Content-Type` header value, without quotes.
+pub fn parameter(content_type: &str, name: &str) -> Option<String> {
+    let mut rest = content_type.split_once(';')?.1;
+
+    loop {
+        let (key, after_key) = match rest.find('=') {
+            Some(index) => (rest[..index].trim(), &rest[index + 1..]),
+            None => return None,
+        };
+        let after_key = after_key.trim_start();
+
+        let (value, after_value) = if let Some(quoted) = after_key.strip_prefix('"') {
+            let end = quoted.find('"')?;
+            (&quoted[..end], &quoted[end + 1..])
+        } else {
+            match after_key.find(';') {
+                Some(end) => (after_key[..end].trim(), &after_key[end..]),
+                None => (after_key.trim(), ""),
+            }
+        };
+
+        if key.eq_ignore_ascii_case(name) {
+            return Some(value.to_string());
+        }
+        rest = match after_value.find(';') {
+            Some(index) => &after_value[index + 1..],
+            None => return None,
+        };
+    }
+}
+
+/// Media type of a `Content-Type` header value, in lowercase.
+pub fn media_type(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn trim_angle_brackets(s: &str) -> &str {
+    s.trim().trim_start_matches('<').trim_end_matches('>')
+}
+
+impl Multipart {
+    /// Create a message with a random boundary.
+    pub fn new(parts: Vec<Part>) -> Self {
+        Multipart {
+            boundary: format!("uuid:{}", random_id()),
+            parts,
+        }
+    }
+
+    /// Root part: the one designated by `start` if any, or the first one.
+    pub fn root(&self, start: Option<&str>) -> Result<&Part, Error> {
+        self.root_index(start).map(|index| &self.parts[index])
+    }
+
+    /// Index of the root part.
+    pub fn root_index(&self, start: Option<&str>) -> Result<usize, Error> {
+        match start {
+            Some(start) => {
+                let start = trim_angle_brackets(start);
+                self.parts
+                    .iter()
+                    .position(|p| p.content_id.as_deref() == Some(start))
+                    .ok_or_else(|| Error::MissingRoot(start.to_string()))
+            }
+            None if self.parts.is_empty() => Err(Error::MissingRoot(String::new())),
+            None => Ok(0),
+        }
+    }
+
+    /// Find a part by content ID, given with or without the `cid:` scheme.
+    pub fn part(&self, content_id: &str) -> Option<&Part> {
+        let content_id = content_id.strip_prefix("cid:").unwrap_or(content_id);
+        self.parts
+            .iter()
+            .find(|p| p.content_id.as_deref() == Some(content_id))
+    }
+
+    /// `Content-Type` header value of the message. `parameters` are added
+    /// after `type` (like `start` or `start-info`).
+    pub fn content_type(&self, root_type: &str, parameters: &[(&str, &str)]) -> String {
+        let mut content_type = format!("multipart/related; type=\"{}\"", root_type);
+        if let Some(start) = self.parts.first().and_then(|p| p.content_id.as_ref()) {
+            content_type.push_str(&format!("; start=\"<{}>\"", start));
+        }
+        for (key, value) in parameters.iter() {
+            content_type.push_str(&format!("; {}=\"{}\"", key, value));
+        }
+        content_type.push_str(&format!("; boundary=\"{}\"", self.boundary));
+        content_type
+    }
+
+    /// Serialize the body of the message.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+
+        for part in self.parts.iter() {
+            out.extend_from_slice(format!("--{}\r\n", self.boundary).as_bytes());
+            out.extend_from_slice(format!("Content-Type: {}\r\n", part.content_type).as_bytes());
+            if let Some(content_id) = part.content_id.as_ref() {
+                out.extend_from_slice(format!("Content-ID: <{}>\r\n", content_id).as_bytes());
+            }
+            for (key, value) in part.headers.iter() {
+                out.extend_from_slice(format!("{}: {}\r\n", key, value).as_bytes());
+            }
+            out.extend_from_slice(b"\r\n");
+            out.extend_from_slice(&part.body);
+            out.extend_from_slice(b"\r\n");
+        }
+        out.extend_from_slice(format!("--{}--\r\n", self.boundary).as_bytes());
+
+        out
+    }
+
+    /// Parse a message, given the value of its `Content-Type` header.
+    pub fn parse(content_type: &str, body: &[u8]) -> Result<Multipart, Error> {
+        let boundary = parameter(content_type, "boundary").ok_or(Error::MissingBoundary)?;
+        let delimiter = format!("--{}", boundary).into_bytes();
+        let separator = format!("\r\n--{}", boundary).into_bytes();
+
+        let mut rest = match find(body, &delimiter) {
+            Some(index) => &body[index + delimiter.len()..],
+            None => return Err(Error::MalformedPart),
+        };
+
+        let mut parts = Vec::new();
+        loop {
+            if rest.starts_with(b"--") {
+                break;
+            }
+            // skip the transport padding and line break after the boundary
+            let start = find(rest, b"\r\n").ok_or(Error::MalformedPart)? + 2;
+            rest = &rest[start..];
+
+            let end = find(rest, &separator).ok_or(Error::MalformedPart)?;
+            parts.push(Multipart::parse_part(&rest[..end])?);
+            rest = &rest[end + separator.len()..];
+        }
+
+        Ok(Multipart { boundary, parts })
+    }
+
+    fn parse_part(part: &[u8]) -> Result<Part, Error> {
+        let (head, body) = if part.starts_with(b"\r\n") {
+            (&part[..0], &part[2..])
+        } else {
+            let index = find(part, b"\r\n\r\n").ok_or(Error::MalformedPart)?;
+            (&part[..index], &part[index + 4..])
+        };
+
+        let mut result = Part::new("text/plain", body.to_vec());
+        let head = String::from_utf8_lossy(head);
+        for line in head.split("\r\n").filter(|l| !l.is_empty()) {
+            let index = line.find(':').ok_or(Error::MalformedPart)?;
+            let key = line[..index].trim();
+            let value = line[index + 1..].trim();
+
+            if key.eq_ignore_ascii_case("Content-Type") {
+                result.content_type = value.to_string();
+            } else if key.eq_ignore_ascii_case("Content-ID") {
+                result.content_id = Some(trim_angle_brackets(value).to_string());
+            } else {
+                result.headers.push((key.to_string(), value.to_string()));
+            }
+        }
+
+        if result
+            .header("Content-Transfer-Encoding")
+            .map(|e| e.eq_ignore_ascii_case("base64"))
+            .unwrap_or(false)
+        {
+            let encoded = result
+                .body
+                .iter()
+                .filter(|b| !b.is_ascii_whitespace())
+                .cloned()
+                .collect::<Vec<_>>();
+            result.body = base64::decode(&encoded).map_err(Error::Base64)?;
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parameters() {
+        let content_type = r#"multipart/related; type="application/xop+xml"; start="<root@savon>";start-info="text/xml"; boundary=simple"#;
+
+        assert_eq!(media_type(content_type), "multipart/related");
+        assert_eq!(
+            parameter(content_type, "type").as_deref(),
+            Some("application/xop+xml")
+        );
+        assert_eq!(
+            parameter(content_type, "start").as_deref(),
+            Some("<root@savon>")
+        );
+        assert_eq!(
+            parameter(content_type, "Boundary").as_deref(),
+            Some("simple")
+        );
+        assert_eq!(parameter(content_type, "charset"), None);
+        assert_eq!(parameter("text/xml", "charset"), None);
+    }
+
+    #[test]
+    fn round_trip() {
+        let message = Multipart::new(vec![
+            Part::new("text/xml", b"<root/>".to_vec()).with_content_id("root@savon"),
+            Part::new(
+                "application/octet-stream",
+                vec![0, 159, 146, 150, b'\r', b'\n'],
+            )
+            .with_content_id("data@savon")
+            .with_header("Content-Transfer-Encoding", "binary"),
+        ]);
+
+        let content_type = message.content_type("text/xml", &[]);
+        let parsed = Multipart::parse(&content_type, &message.to_bytes()).unwrap();
+        assert_eq!(parsed, message);
+        assert_eq!(
+            parsed.root(Some("<root@savon>")).unwrap().body,
+            b"<root/>".to_vec()
+        );
+        assert_eq!(
+            parsed
+                .part("cid:data@savon")
+                .unwrap()
+                .header("content-transfer-encoding"),
+            Some("binary")
+        );
+    }
+
+    #[test]
+    fn parse_with_preamble() {
+        let body = "preamble\r\n--b\r\nContent-Type: text/xml\r\n\r\n<a/>\r\n--b\r\nContent-ID: <x>\r\nContent-Transfer-Encoding: base64\r\n\r\naGVs\r\nbG8=\r\n--b--\r\nepilogue";
+        let message = Multipart::parse("multipart/related; boundary=b", body.as_bytes()).unwrap();
+
+        assert_eq!(message.parts.len(), 2);
+        assert_eq!(message.root(None).unwrap().body, b"<a/>".to_vec());
+        assert_eq!(message.part("x").unwrap().body, b"hello".to_vec());
+        assert_eq!(
+            message.root(Some("<missing>")),
+            Err(Error::MissingRoot("missing".to_string()))
+        );
+        assert_eq!(
+            Multipart::parse("multipart/related", body.as_bytes()),
+            Err(Error::MissingBoundary)
+        );
+    }
+}
@@ -0,0 +1,382 @@
+//! MTOM/XOP encoding of binary content.
+//!
+//! Generated clients serialize `base64Binary` fields with `binary_element`,
+//! which marks the element. When MTOM is enabled for an operation, the
+//! message is serialized inside `collect`: the raw content of marked
+//! elements is kept in a side table keyed by Content-ID, then moved to a
+//! separate `multipart/related` part by `extract`, the element holding an
+//! `xop:Include` reference. Otherwise, the content is sent inline, as base64
+//! text.
+//!
+//! Responses sent as `multipart/related` are decoded whatever the policy:
+//! the referenced parts are returned in a side table, which `as_binary`
+//! reads from while the output message is deserialized in `with_binaries`.
+//!
+//! The side table is set for the current thread while a synchronous closure
+//! runs, and the previous one is restored afterwards, even if the closure
+//! panics. Messages built outside `collect` carry markers, removed by
+//! `strip_markers` before they are sent.
+
+use crate::mime::{self, Multipart, Part, ROOT_CONTENT_ID};
+use crate::rpser::xml::BuildElement;
+use crate::rpser::RpcError;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use xmltree::{Element, XMLNode};
+
+pub const XOP_NS: &str = "http://www.w3.org/2004/08/xop/include";
+pub const XOP_MEDIA_TYPE: &str = "application/xop+xml";
+
+/// Attribute marking the elements holding binary content. It is removed
+/// before the envelope is sent. Its value is `true` for inline content, or
+/// the Content-ID of the content in the side table.
+const BINARY_MARKER: &str = "savon-binary";
+
+/// Binary contents, keyed by Content-ID (without `cid:`).
+pub type Binaries = HashMap<String, Vec<u8>>;
+
+thread_local! {
+    static BINARIES: RefCell<Option<Binaries>> = const { RefCell::new(None) };
+}
+
+/// Restores the previous side table when dropped.
+struct Restore(Option<Binaries>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        BINARIES.with(|b| b.replace(previous));
+    }
+}
+
+/// Run `f` with `table` as the side table, returning its result and the
+/// table.
+fn scoped<T, F: FnOnce() -> T>(table: Binaries, f: F) -> (T, Binaries) {
+    let _restore = Restore(BINARIES.with(|b| b.replace(Some(table))));
+    let result = f();
+    let table = BINARIES.with(|b| b.take()).unwrap_or_default();
+    (result, table)
+}
+
+/// Serialize a message with `f`, keeping the content of the binary
+/// elements in the returned table instead of encoding it.
+pub fn collect<T, F: FnOnce() -> T>(f: F) -> (T, Binaries) {
+    scoped(Binaries::new(), f)
+}
+
+/// Deserialize a message with `f`, the `xop:Include` references being
+/// resolved from `binaries` by `BuildElement::as_binary`.
+pub fn with_binaries<T, F: FnOnce() -> T>(binaries: Binaries, f: F) -> T {
+    scoped(binaries, f).0
+}
+
+/// Get a content from the current side table. `href` is the `xop:Include`
+/// reference.
+pub(crate) fn get(href: &str) -> Option<Vec<u8>> {
+    let content_id = href.strip_prefix("cid:").unwrap_or(href);
+    BINARIES.with(|b| b.borrow().as_ref()?.get(content_id).cloned())
+}
+
+/// Build an element holding binary content.
+///
+/// Inside `collect`, the content is kept in the side table, otherwise it is
+/// encoded as base64 text.
+pub fn binary_element(name: &str, data: &[u8]) -> Element {
+    let element = Element::node(name);
+    let stored = BINARIES.with(|b| {
+        let mut b = b.borrow_mut();
+        let table = b.as_mut()?;
+        let content_id = format!("{}@savon", mime::random_id());
+        table.insert(content_id.clone(), data.to_vec());
+        Some(content_id)
+    });
+
+    match stored {
+        Some(content_id) => element.with_attr(BINARY_MARKER, content_id),
+        None => element
+            .with_attr(BINARY_MARKER, "true")
+            .with_text(base64::encode(data)),
+    }
+}
+
+/// `href` of the `xop:Include` child of an element, if it has one.
+pub fn include_href(element: &Element) -> Option<&str> {
+    element
+        .children
+        .iter()
+        .filter_map(|c| c.as_element())
+        .find(|c| c.name == "Include" && c.namespace.as_deref() == Some(XOP_NS))
+        .and_then(|include| include.attributes.get("href"))
+        .map(|href| href.as_str())
+}
+
+fn for_each_element<F: FnMut(&mut Element)>(element: &mut Element, f: &mut F) {
+    f(element);
+    for child in element.children.iter_mut() {
+        if let XMLNode::Element(child) = child {
+            for_each_element(child, f);
+        }
+    }
+}
+
+/// Remove the binary markers, keeping the content inline.
+pub fn strip_markers(envelope: &mut Element) {
+    for_each_element(envelope, &mut |element| {
+        element.attributes.remove(BINARY_MARKER);
+    });
+}
+
+/// Move the content of the binary elements to separate parts, replacing it
+/// with `xop:Include` references. `binaries` is the table filled by
+/// `collect`; inline content is decoded from base64.
+pub fn extract(envelope: &mut Element, binaries: &mut Binaries) -> Vec<Part> {
+    let mut parts = Vec::new();
+
+    for_each_element(envelope, &mut |element| {
+        let marker = match element.attributes.remove(BINARY_MARKER) {
+            Some(marker) => marker,
+            None => return,
+        };
+
+        let (content_id, data) = match binaries.remove(&marker) {
+            Some(data) => (marker, data),
+            None => {
+                let text = element.get_text().unwrap_or_default();
+                match base64::decode(text.split_whitespace().collect::<String>()) {
+                    Ok(data) => (format!("{}@savon", mime::random_id()), data),
+                    // not produced by `binary_element`, keep it inline
+                    Err(_) => return,
+                }
+            }
+        };
+
+        element.children = vec![XMLNode::Element(
+            Element::node("xop:Include")
+                .with_attr("xmlns:xop", XOP_NS)
+                .with_attr("href", format!("cid:{}", content_id)),
+        )];
+        parts.push(
+            Part::new("application/octet-stream", data)
+                .with_content_id(content_id)
+                .with_header("Content-Transfer-Encoding", "binary"),
+        );
+    });
+
+    parts
+}
+
+/// Build an MTOM message from the serialized envelope and the parts
+/// returned by `extract`. Returns the `Content-Type` header value and the
+/// body.
+pub fn encode(envelope: String, parts: Vec<Part>) -> (String, Vec<u8>) {
+    let root = Part::new(
+        format!("{}; charset=UTF-8; type=\"text/xml\"", XOP_MEDIA_TYPE),
+        envelope.into_bytes(),
+    )
+    .with_content_id(ROOT_CONTENT_ID)
+    .with_header("Content-Transfer-Encoding", "8bit");
+
+    let mut message = Multipart::new(vec![root]);
+    message.parts.extend(parts);
+
+    let content_type = message.content_type(XOP_MEDIA_TYPE, &[("start-info", "text/xml")]);
+    (content_type, message.to_bytes())
+}
+
+/// Decode a `multipart/related` message, returning the root envelope as is,
+/// the parts referenced by `xop:Include` elements keyed by Content-ID, and
+/// the other parts (SOAP with Attachments).
+pub fn decode(
+    content_type: &str,
+    body: &[u8],
+) -> Result<(Vec<u8>, Binaries, Vec<Part>), crate::Error> {
+    let message = Multipart::parse(content_type, body)?;
+    let start = mime::parameter(content_type, "start");
+    let root_index = message.root_index(start.as_deref())?;
+
+    let mut envelope = Element::parse(message.parts[root_index].body.as_slice()).map_err(|e| {
+        RpcError::InvalidXml {
+            error: e.to_string(),
+        }
+    })?;
+
+    let mut included = Vec::new();
+    let mut missing = None;
+    for_each_element(&mut envelope, &mut |element| {
+        if let Some(href) = include_href(element) {
+            match message.part(href) {
+                Some(part) => included.push(part.content_id.clone()),
+                None => missing = Some(href.to_string()),
+            }
+        }
+    });
+    if let Some(href) = missing {
+        return Err(mime::Error::MissingPart(href).into());
+    }
+
+    let mut root = Vec::new();
+    let mut binaries = Binaries::new();
+    let mut others = Vec::new();
+    for (index, part) in message.parts.into_iter().enumerate() {
+        if index == root_index {
+            root = part.body;
+        } else if included.contains(&part.content_id) {
+            binaries.insert(part.content_id.unwrap_or_default(), part.body);
+        } else {
+            others.push(part);
+        }
+    }
+    Ok((root, binaries, others))
+}
+
+/// Like `decode`, with the `xop:Include` references replaced by the base64
+/// encoded parts, so that the envelope can be read without a side table.
+pub fn decode_inline(
+    content_type: &str,
+    body: &[u8],
+) -> Result<(Vec<u8>, Vec<Part>), crate::Error> {
+    let (root, binaries, others) = decode(content_type, body)?;
+    if binaries.is_empty() {
+        return Ok((root, others));
+    }
+
+    let mut envelope = Element::parse(root.as_slice()).map_err(|e| RpcError::InvalidXml {
+        error: e.to_string(),
+    })?;
+    for_each_element(&mut envelope, &mut |element| {
+        let data = include_href(element)
+            .map(|href| href.strip_prefix("cid:").unwrap_or(href))
+            .and_then(|content_id| binaries.get(content_id));
+        if let Some(data) = data {
+            element.children = vec![XMLNode::Text(base64::encode(data))];
+        }
+    });
+    Ok((envelope.to_string().into_bytes(), others))
+}
+
+/// Per client and per operation MTOM setting.
+#[derive(Clone, Debug, Default)]
+pub struct MtomPolicy {
+    /// Setting for the operations not listed in `operations`.
+    pub enabled: bool,
+    pub operations: HashMap<String, bool>,
+}
+
+impl MtomPolicy {
+    /// MTOM for all operations.
+    pub fn enabled() -> Self {
+        MtomPolicy {
+            enabled: true,
+            operations: HashMap::new(),
+        }
+    }
+
+    /// No MTOM, the default.
+    pub fn disabled() -> Self {
+        MtomPolicy::default()
+    }
+
+    /// Override the setting for one operation.
+    pub fn with_operation<S: Into<String>>(mut self, operation: S, enabled: bool) -> Self {
+        self.operations.insert(operation.into(), enabled);
+        self
+    }
+
+    pub fn is_enabled(&self, operation: &str) -> bool {
+        self.operations
+            .get(operation)
+            .cloned()
+            .unwrap_or(self.enabled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpser::{Method, Response};
+
+    const PDF: &[u8] = b"%PDF-1.4\r\n\x00\x01\x02\xff";
+
+    fn envelope() -> Element {
+        Method::new("Store")
+            .with(Element::node("name").with_text("doc.pdf"))
+            .with(binary_element("content", PDF))
+            .as_element("urn:archive")
+    }
+
+    #[test]
+    fn inline_without_mtom() {
+        let mut envelope = envelope();
+        strip_markers(&mut envelope);
+
+        let xml = envelope.to_string();
+        assert!(!xml.contains(BINARY_MARKER));
+        assert!(xml.contains(&format!("<content>{}</content>", base64::encode(PDF))));
+    }
+
+    #[test]
+    fn round_trip() {
+        let (mut envelope, mut binaries) = collect(envelope);
+        assert_eq!(binaries.len(), 1);
+        assert!(!envelope.to_string().contains(&base64::encode(PDF)));
+
+        let parts = extract(&mut envelope, &mut binaries);
+        assert!(binaries.is_empty());
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].body, PDF.to_vec());
+
+        let xml = envelope.to_string();
+        assert!(!xml.contains(BINARY_MARKER));
+        assert!(xml.contains("<xop:Include "));
+        assert!(xml.contains(&format!(
+            r#"href="cid:{}""#,
+            parts[0].content_id.as_ref().unwrap()
+        )));
+
+        let (content_type, body) = encode(xml, parts);
+        assert!(content_type.starts_with(
+            r#"multipart/related; type="application/xop+xml"; start="<root.message@savon>""#
+        ));
+
+        let (decoded, binaries, others) = decode(&content_type, &body).unwrap();
+        assert!(others.is_empty());
+        assert_eq!(binaries.len(), 1);
+        let decoded = String::from_utf8(decoded).unwrap();
+        assert!(!decoded.contains(&base64::encode(PDF)));
+
+        let response = Response::from_xml(&decoded).unwrap();
+        let content = response.body.get_child("content").unwrap();
+        assert!(content.as_binary().is_err());
+        // the same part can be read twice
+        let data = with_binaries(binaries, || (content.as_binary(), content.as_binary()));
+        assert_eq!(data, (Ok(PDF.to_vec()), Ok(PDF.to_vec())));
+        assert!(content.as_binary().is_err());
+
+        let (inline, _) = decode_inline(&content_type, &body).unwrap();
+        let response = Response::from_xml(&String::from_utf8(inline).unwrap()).unwrap();
+        let content = response.body.get_child("content").unwrap();
+        assert_eq!(content.as_binary().unwrap(), PDF.to_vec());
+    }
+
+    #[test]
+    fn restores_the_table_on_panic() {
+        let binaries = vec![("part@savon".to_string(), PDF.to_vec())]
+            .into_iter()
+            .collect();
+        let result = std::panic::catch_unwind(|| with_binaries(binaries, || panic!("decoder")));
+        assert!(result.is_err());
+        assert_eq!(get("cid:part@savon"), None);
+        assert!(binary_element("content", PDF).get_text().is_some());
+    }
+
+    #[test]
+    fn policy() {
+        let policy = MtomPolicy::enabled().with_operation("Ping", false);
+        assert!(policy.is_enabled("Store"));
+        assert!(!policy.is_enabled("Ping"));
+
+        let policy = MtomPolicy::disabled().with_operation("Store", true);
+        assert!(policy.is_enabled("Store"));
+        assert!(!policy.is_enabled("Ping"));
+    }
+}
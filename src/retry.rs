@@ -0,0 +1,360 @@
+//! Retry of failed calls.
+//!
+//! Calls are only retried for operations marked as idempotent, with
+//! exponential backoff and jitter between attempts:
+//!
+//! ```rust,ignore
+//! let client = soap::StockQuoteService::new(url).with_retries(
+//!     Retries::new(
+//!         RetryPolicy::new(3)
+//!             .with_status(503)
+//!             .with_fault_code("Server.Busy"),
+//!     )
+//!     .with_idempotent("GetLastTradePrice"),
+//! );
+//! ```
+
+use crate::rpser::RpcError;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+/// When and how often a call is retried.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the first retry.
+    pub initial_backoff: Duration,
+    /// Upper bound of the delay between attempts.
+    pub max_backoff: Duration,
+    /// Factor applied to the delay after each attempt.
+    pub multiplier: f64,
+    /// Fraction of the delay that is randomized, between 0 and 1.
+    pub jitter: f64,
+    /// Retry when the request could not be sent, or timed out.
+    pub connection_errors: bool,
+    /// Retry on these HTTP statuses (for responses without a fault).
+    pub statuses: Vec<u16>,
+    /// Retry on these fault codes, compared with or without their prefix.
+    pub fault_codes: Vec<String>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::new(1)
+    }
+}
+
+impl RetryPolicy {
+    /// Policy making up to `max_attempts` attempts on connection errors,
+    /// waiting 100ms before the first retry, then doubling the delay up to
+    /// 10 seconds.
+    pub fn new(max_attempts: u32) -> Self {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: 0.5,
+            connection_errors: true,
+            statuses: vec![],
+            fault_codes: vec![],
+        }
+    }
+
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_connection_errors(mut self, retry: bool) -> Self {
+        self.connection_errors = retry;
+        self
+    }
+
+    /// Also retry on this HTTP status.
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.statuses.push(status);
+        self
+    }
+
+    /// Also retry on this fault code, like `Server.Busy`.
+    pub fn with_fault_code<S: Into<String>>(mut self, fault_code: S) -> Self {
+        self.fault_codes.push(fault_code.into());
+        self
+    }
+
+    /// Whether the call should be attempted again after this error.
+    pub fn is_retryable(&self, error: &crate::Error) -> bool {
+        match error {
+            crate::Error::Reqwest(e) => {
+                self.connection_errors && (e.is_connect() || e.is_timeout() || e.is_request())
+            }
+            crate::Error::Transport(_) => self.connection_errors,
+            crate::Error::UnexpectedResponse { status, .. } => self.statuses.contains(status),
+            crate::Error::Rpc(RpcError::Fault { fault_code, .. }) => {
+                let local = fault_code.splitn(2, ':').last().unwrap_or_default();
+                self.fault_codes
+                    .iter()
+                    .any(|code| code == fault_code || code == local)
+            }
+            _ => false,
+        }
+    }
+
+    /// Delay before the given retry (starting at 1), with jitter.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exponent = retry.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let delay = self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent);
+        let delay = delay.min(self.max_backoff.as_secs_f64());
+        let jitter = self.jitter * rand::random::<f64>();
+
+        Duration::from_secs_f64(delay * (1.0 - jitter))
+    }
+
+    /// Delay before the next attempt, or `None` if the call should not be
+    /// retried.
+    pub fn next_delay(&self, attempt: u32, error: &crate::Error) -> Option<Duration> {
+        if attempt < self.max_attempts && self.is_retryable(error) {
+            Some(self.backoff(attempt))
+        } else {
+            None
+        }
+    }
+}
+
+/// Retry settings of a client.
+#[derive(Clone, Debug, Default)]
+pub struct Retries {
+    /// Policy of the operations not listed in `operations`.
+    pub policy: RetryPolicy,
+    pub operations: HashMap<String, RetryPolicy>,
+    /// Only idempotent operations are retried.
+    pub idempotent: HashSet<String>,
+}
+
+impl Retries {
+    pub fn new(policy: RetryPolicy) -> Self {
+        Retries {
+            policy,
+            operations: HashMap::new(),
+            idempotent: HashSet::new(),
+        }
+    }
+
+    /// Use a specific policy for an operation.
+    pub fn with_operation<S: Into<String>>(mut self, operation: S, policy: RetryPolicy) -> Self {
+        self.operations.insert(operation.into(), policy);
+        self
+    }
+
+    /// Mark an operation as idempotent, so it can be retried.
+    pub fn with_idempotent<S: Into<String>>(mut self, operation: S) -> Self {
+        self.idempotent.insert(operation.into());
+        self
+    }
+
+    /// Policy of an operation, `None` if it is not idempotent.
+    pub fn policy(&self, operation: &str) -> Option<&RetryPolicy> {
+        if !self.idempotent.contains(operation) {
+            return None;
+        }
+        Some(self.operations.get(operation).unwrap_or(&self.policy))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gen::{FromElement, ToElements};
+    use crate::http::{request_response, Call, CallOptions};
+    use crate::interceptor::Chain;
+    use crate::rpser::xml::BuildElement;
+    use crate::transport::ReqwestTransport;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use xmltree::Element;
+
+    #[derive(Debug)]
+    struct Echo(String);
+
+    impl ToElements for Echo {
+        fn to_elements(&self) -> Vec<Element> {
+            vec![Element::node("value").with_text(self.0.clone())]
+        }
+    }
+
+    impl FromElement for Echo {
+        fn from_element(element: &Element) -> Result<Self, crate::Error> {
+            let value = element.get_at_path(&["value"])?;
+            Ok(Echo(value.get_text().unwrap_or_default().to_string()))
+        }
+    }
+
+    const PONG: &str = r#"<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/"><soap:Body><ns:EchoResponse xmlns:ns="urn:test"><value>pong</value></ns:EchoResponse></soap:Body></soap:Envelope>"#;
+    const BUSY: &str = r#"<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/"><soap:Body><soap:Fault><faultcode>soap:Server.Busy</faultcode><faultstring>busy</faultstring></soap:Fault></soap:Body></soap:Envelope>"#;
+
+    /// Serve the given responses in order, one per connection, counting
+    /// the requests.
+    fn flaky_server(responses: Vec<(u16, &'static str)>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/echo", listener.local_addr().unwrap());
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+
+        std::thread::spawn(move || {
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    let lower = line.to_ascii_lowercase();
+                    if let Some(value) = lower.strip_prefix("content-length:") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut request = vec![0; content_length];
+                reader.read_exact(&mut request).unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+
+                write!(
+                    stream,
+                    "HTTP/1.1 {} Status\r\nContent-Type: text/xml\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+
+        (url, hits)
+    }
+
+    fn fast(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy::new(max_attempts)
+            .with_backoff(Duration::from_millis(1), Duration::from_millis(5))
+            .with_status(503)
+            .with_fault_code("Server.Busy")
+    }
+
+    async fn call(url: &str, retries: &Retries) -> Result<Result<Echo, ()>, crate::Error> {
+        let interceptors = Chain::new();
+        let call = Call {
+            base_url: url,
+            namespace: "urn:test",
+            port_type: "TestPortType",
+            operation: "Echo",
+            input: "EchoRequest",
+            soap_action: None,
+            mtom: false,
+            retry: retries.policy("Echo"),
+            interceptors: &interceptors,
+            options: &CallOptions::default(),
+        };
+        request_response(
+            &ReqwestTransport::default(),
+            &call,
+            &Echo("ping".to_string()),
+        )
+        .await
+    }
+
+    #[test]
+    fn backoff() {
+        let policy = RetryPolicy::new(5)
+            .with_backoff(Duration::from_millis(100), Duration::from_millis(300))
+            .with_jitter(0.0);
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(300));
+        assert_eq!(policy.backoff(10), Duration::from_millis(300));
+
+        let policy = policy.with_jitter(0.5);
+        for _ in 0..100 {
+            let delay = policy.backoff(2);
+            assert!(delay > Duration::from_millis(100) && delay <= Duration::from_millis(200));
+        }
+    }
+
+    #[test]
+    fn idempotent_operations_only() {
+        let retries = Retries::new(fast(3))
+            .with_operation("Echo", fast(5))
+            .with_idempotent("Echo");
+
+        assert_eq!(retries.policy("Echo").unwrap().max_attempts, 5);
+        assert!(retries.policy("Store").is_none());
+    }
+
+    #[tokio::test]
+    async fn retries_statuses_and_faults() {
+        let (url, hits) = flaky_server(vec![(503, ""), (500, BUSY), (200, PONG)]);
+        let retries = Retries::new(fast(3)).with_idempotent("Echo");
+
+        let res = call(&url, &retries).await.unwrap();
+        assert_eq!(res.unwrap().0, "pong");
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let (url, hits) = flaky_server(vec![(503, ""), (503, ""), (200, PONG)]);
+        let retries = Retries::new(fast(2)).with_idempotent("Echo");
+
+        match call(&url, &retries).await {
+            Err(crate::Error::UnexpectedResponse { status: 503, .. }) => {}
+            other => panic!("expected a 503 error, got {:?}", other),
+        }
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_non_idempotent_operations() {
+        let (url, hits) = flaky_server(vec![(503, ""), (200, PONG)]);
+        let retries = Retries::new(fast(3));
+
+        assert!(call(&url, &retries).await.is_err());
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_connection_errors() {
+        // reserve a port, then close it so connections are refused
+        let url = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("http://{}/echo", listener.local_addr().unwrap())
+        };
+
+        let retryable =
+            |policy: RetryPolicy| policy.next_delay(1, &crate::Error::Transport("refused".into()));
+        assert!(retryable(fast(3)).is_some());
+        assert!(retryable(fast(3).with_connection_errors(false)).is_none());
+        assert!(retryable(fast(1)).is_none());
+
+        let retries = Retries::new(fast(3)).with_idempotent("Echo");
+        match call(&url, &retries).await {
+            Err(crate::Error::Reqwest(e)) => assert!(e.is_connect()),
+            other => panic!("expected a connection error, got {:?}", other),
+        }
+    }
+}
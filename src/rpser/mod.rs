@@ -34,10 +34,16 @@ impl Method {
 
     /// Convert method to full XML envelope.
     pub fn as_xml(&self, api_url: &str) -> String {
+        self.as_element(api_url).to_string()
+    }
+
+    /// Convert method to the full envelope element.
+    pub fn as_element(&self, api_url: &str) -> Element {
         let namespace = "ns";
 
-        let envelope = Element::node("soap:Envelope")
+        Element::node("soap:Envelope")
             .with_attr("xmlns:soap", "http://schemas.xmlsoap.org/soap/envelope/")
+            .with_attr("xmlns:xsi", "http://www.w3.org/2001/XMLSchema-instance")
             .with_attr(format!("xmlns:{}", namespace), api_url)
             .with_children(vec![
                 Element::node("soap:Header"),
@@ -45,15 +51,15 @@ impl Method {
                     Element::node(format!("{}:{}", namespace, self.name))
                         .with_children_from_iter(self.args.iter()),
                 ),
-            ]);
-
-        envelope.to_string()
+            ])
     }
 }
 
 /// XML response representation.
 #[derive(Debug)]
 pub struct Response {
+    /// Content of the `Header` element, if the envelope has one.
+    pub header: Option<Element>,
     pub body: Element,
 }
 
@@ -61,11 +67,14 @@ impl Response {
     /// Parse response from XML.
     pub fn from_xml(xml: &str) -> Result<Response> {
         let mut bytes = xml.as_bytes();
-        let mut element = Element::parse(&mut bytes).unwrap();
+        let mut element = Element::parse(&mut bytes).map_err(|e| RpcError::InvalidXml {
+            error: e.to_string(),
+        })?;
 
         if element.name != "Envelope" {
             return Err(RpcError::UnexpectedElement { tag: element.name });
         }
+        let header = element.get_child("Header").cloned();
         element = element.descend(&["Body"])?;
         element = element.descend_first()?;
 
@@ -81,11 +90,18 @@ impl Response {
                     .get_text()
                     .map(|t| t.to_string())
                     .unwrap_or_default(),
-                fault_detail: Box::new(element.get_at_path(&["detail"])?),
+                fault_detail: Box::new(
+                    element
+                        .get_at_path(&["detail"])
+                        .unwrap_or_else(|_| Element::node("detail")),
+                ),
             });
         }
 
-        Ok(Response { body: element })
+        Ok(Response {
+            header,
+            body: element,
+        })
     }
 }
 
@@ -106,6 +122,9 @@ pub enum RpcError {
     XmlError {
         error: self::xml::Error,
     },
+    InvalidXml {
+        error: String,
+    },
     ExpectedElementText {
         tag: String,
     },
@@ -131,7 +150,7 @@ pub type Result<T> = result::Result<T, RpcError>;
 #[cfg(test)]
 mod test {
     use super::*;
-    use rpser::xml::BuildElement;
+    use crate::rpser::xml::BuildElement;
 
     #[test]
     fn can_deal_with_fault() {
@@ -183,7 +202,7 @@ mod test {
                 assert_eq!(response.body.name, "loginResponse");
                 let return_element = response.body.descend_first().unwrap();
                 assert_eq!(return_element.name, "loginReturn");
-                assert_eq!(return_element.text, Some("a3a8ecc6d5".into()));
+                assert_eq!(return_element.get_text(), Some("a3a8ecc6d5".into()));
             }
             other => panic!(
                 "expected to receive fault in this test, received {:?}",
@@ -191,4 +210,46 @@ mod test {
             ),
         };
     }
+
+    #[test]
+    fn nil_elements() {
+        let request = Method::new("SetNote")
+            .with(Element::node("note").with_attr("xsi:nil", "true"))
+            .as_xml("urn:notes");
+        let response = Response::from_xml(&request.replace("SetNote", "SetNoteResponse")).unwrap();
+
+        let note = response.body.get_child("note").unwrap();
+        assert!(note.is_nil());
+        assert!(!Element::node("note").is_nil());
+        assert!(Element::node("note").with_attr("xsi:nil", "1").is_nil());
+    }
+
+    #[test]
+    fn typed_values() {
+        let literal = Element::node("count").with_text(" 42 ");
+        assert_eq!(literal.as_long(), Ok(42));
+        assert_eq!(literal.expect_type("int"), Ok(()));
+        assert_eq!(Element::node("ok").with_text("1").as_boolean(), Ok(true));
+        assert!(Element::node("ok").with_text("yes").as_boolean().is_err());
+        assert_eq!(Element::node("name").as_string(), Ok(String::new()));
+        assert_eq!(
+            Element::node("price").with_text("\n 1.5 ").as_double(),
+            Ok(1.5)
+        );
+        assert!(Element::node("price").as_double().is_err());
+
+        let encoded = Element::node("count")
+            .with_attr("xsi:type", "xsd:int")
+            .with_text("42");
+        assert_eq!(encoded.as_int(), Ok(42));
+        assert_eq!(encoded.expect_type("int"), Ok(()));
+        assert_eq!(
+            encoded.expect_type("string"),
+            Err(xml::Error::ExpectedElementWithType {
+                name: "count".to_string(),
+                expected_type: "*:string".to_string(),
+                given: Some("xsd:int".to_string()),
+            })
+        );
+    }
 }
@@ -1,9 +1,11 @@
 //! Helper trait to deal with XML Element tree.
 
+pub mod c14n;
+
 use chrono::offset::Utc;
 use chrono::{DateTime, ParseError};
 use std::collections::HashMap;
-use std::num::ParseIntError;
+use std::num::{ParseFloatError, ParseIntError};
 use xmltree::Element;
 
 #[derive(Debug, PartialEq)]
@@ -21,7 +23,19 @@ pub enum Error {
     /// Can't parse received element.
     ParseIntError { name: String, inner: ParseIntError },
     /// Can't parse received element.
+    ParseFloatError {
+        name: String,
+        inner: ParseFloatError,
+    },
+    /// Can't parse received element.
     ParseDateTimeError { name: String, inner: ParseError },
+    /// Can't parse received element.
+    ParseBase64Error {
+        name: String,
+        inner: base64::DecodeError,
+    },
+    /// The MTOM part referenced by the element was not found.
+    MissingPart { name: String, href: String },
     /// empty
     Empty,
 }
@@ -35,10 +49,10 @@ pub enum Error {
 ///
 /// ```rust
 /// extern crate xmltree;
-/// extern crate confluence;
+/// extern crate savon;
 ///
 /// use xmltree::Element;
-/// use confluence::rpser::xml::BuildElement;
+/// use savon::rpser::xml::BuildElement;
 ///
 /// fn main() {
 ///     assert_eq!(
@@ -95,12 +109,22 @@ pub trait BuildElement {
     /// Get clone of child element at path.
     fn get_at_path(&self, path: &[&str]) -> Result<Element, Error>;
 
+    /// Whether the element is marked with `xsi:nil="true"`.
+    fn is_nil(&self) -> bool;
+
+    /// Check the `xsi:type` of the element, if it has one, against the
+    /// expected XSD type name.
+    fn expect_type(&self, value_type: &str) -> Result<(), Error>;
+
     /// Extract the value of `long` type from the text.
     fn as_long(&self) -> Result<i64, Error>;
 
     /// Extract the value of `int` type from the text.
     fn as_int(&self) -> Result<i32, Error>;
 
+    /// Extract the value of `double` (or `float`) type from the text.
+    fn as_double(&self) -> Result<f64, Error>;
+
     /// Extract the value of `boolean` type from the text.
     fn as_boolean(&self) -> Result<bool, Error>;
 
@@ -109,6 +133,10 @@ pub trait BuildElement {
 
     /// Extract the value of `DateTime` type from the text.
     fn as_datetime(&self) -> Result<DateTime<Utc>, Error>;
+
+    /// Extract the value of `base64Binary` type from the text, or from the
+    /// MTOM part referenced by an `xop:Include` child.
+    fn as_binary(&self) -> Result<Vec<u8>, Error>;
 }
 
 impl BuildElement for Element {
@@ -192,7 +220,7 @@ impl BuildElement for Element {
     fn to_string(&self) -> String {
         let mut xml = Vec::new();
         self.write(&mut xml)
-            .unwrap_or_else(|e| println!("Unable to write xml: {:?}", e));
+            .unwrap_or_else(|e| error!("Unable to write xml: {:?}", e));
         String::from_utf8_lossy(&xml).into_owned()
     }
 
@@ -251,8 +279,33 @@ impl BuildElement for Element {
         }
     }
 
+    fn is_nil(&self) -> bool {
+        // parsed attributes lose their prefix
+        self.attributes
+            .get("nil")
+            .or_else(|| self.attributes.get("xsi:nil"))
+            .is_some_and(|v| v == "true" || v == "1")
+    }
+
+    fn expect_type(&self, value_type: &str) -> Result<(), Error> {
+        match self
+            .attributes
+            .get("type")
+            .or_else(|| self.attributes.get("xsi:type"))
+        {
+            Some(given) if given.rsplit(':').next() != Some(value_type) => {
+                Err(Error::ExpectedElementWithType {
+                    name: self.name.clone(),
+                    expected_type: ["*:", value_type].concat(),
+                    given: Some(given.clone()),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
     fn as_int(&self) -> Result<i32, Error> {
-        let text = get_typed_string(self, "int")?;
+        let text = get_collapsed_string(self);
         Ok(match text.parse() {
             Ok(ref value) => *value,
             Err(e) => {
@@ -265,7 +318,7 @@ impl BuildElement for Element {
     }
 
     fn as_long(&self) -> Result<i64, Error> {
-        let text = get_typed_string(self, "long")?;
+        let text = get_collapsed_string(self);
         Ok(match text.parse() {
             Ok(ref value) => *value,
             Err(e) => {
@@ -277,12 +330,20 @@ impl BuildElement for Element {
         })
     }
 
+    fn as_double(&self) -> Result<f64, Error> {
+        let text = get_collapsed_string(self);
+        text.parse().map_err(|e| Error::ParseFloatError {
+            name: self.name.clone(),
+            inner: e,
+        })
+    }
+
     fn as_string(&self) -> Result<String, Error> {
-        get_typed_string(self, "string")
+        Ok(self.get_text().unwrap_or_default().into_owned())
     }
 
     fn as_datetime(&self) -> Result<DateTime<Utc>, Error> {
-        let text = get_typed_string(self, "dateTime")?;
+        let text = get_collapsed_string(self);
         Ok(match text.parse::<DateTime<Utc>>() {
             Ok(ref value) => *value,
             Err(e) => {
@@ -295,22 +356,37 @@ impl BuildElement for Element {
     }
 
     fn as_boolean(&self) -> Result<bool, Error> {
-        let text = get_typed_string(self, "boolean")?;
-        Ok(text == "true")
+        match get_collapsed_string(self).as_str() {
+            "true" | "1" => Ok(true),
+            "false" | "0" => Ok(false),
+            text => Err(Error::ExpectedElementWithType {
+                name: self.name.clone(),
+                expected_type: "boolean".to_string(),
+                given: Some(text.to_string()),
+            }),
+        }
     }
-}
 
-fn get_typed_string(element: &Element, value_type: &str) -> Result<String, Error> {
-    Ok(
-        match (element.attributes.get("type"), &element.get_text()) {
-            (Some(value), &Some(ref text)) if value.ends_with(value_type) => text.to_string(),
-            (other_type, _) => {
-                return Err(Error::ExpectedElementWithType {
-                    name: element.name.clone(),
-                    expected_type: ["*:", value_type].concat(),
-                    given: other_type.cloned(),
-                });
+    fn as_binary(&self) -> Result<Vec<u8>, Error> {
+        if let Some(href) = crate::mtom::include_href(self) {
+            return crate::mtom::get(href).ok_or_else(|| Error::MissingPart {
+                name: self.name.clone(),
+                href: href.to_string(),
+            });
+        }
+
+        let text = self.get_text().unwrap_or_default();
+        base64::decode(text.split_whitespace().collect::<String>()).map_err(|e| {
+            Error::ParseBase64Error {
+                name: self.name.clone(),
+                inner: e,
             }
-        },
-    )
+        })
+    }
+}
+
+/// Text of an element with a non-string XSD type, with the surrounding
+/// whitespace removed. The type comes from the schema, not from `xsi:type`.
+fn get_collapsed_string(element: &Element) -> String {
+    element.get_text().unwrap_or_default().trim().to_string()
 }
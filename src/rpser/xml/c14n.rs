@@ -0,0 +1,627 @@
+//! Canonical XML 1.0 and Exclusive XML Canonicalization 1.0.
+//!
+//! Canonicalization works on the serialized document rather than on
+//! `xmltree::Element`, since parsing into an `Element` loses the attribute
+//! prefixes and whitespace, which are part of the canonical form.
+//!
+//! The document type declaration is not read: default attributes declared
+//! in a DTD are not added, attribute values are normalized as `CDATA`
+//! whatever their declared type, and entities other than the predefined
+//! ones and character references are not expanded.
+//!
+//! See <https://www.w3.org/TR/xml-c14n/> and
+//! <https://www.w3.org/TR/xml-exc-c14n/>.
+
+use std::collections::{BTreeMap, BTreeSet};
+use xml::attribute::OwnedAttribute;
+use xml::name::OwnedName;
+use xml::namespace::Namespace;
+use xml::reader::{ParserConfig, XmlEvent};
+use xmltree::Element;
+
+/// Canonicalization error.
+#[derive(Debug)]
+pub enum Error {
+    /// The document could not be parsed.
+    Xml(xml::reader::Error),
+    /// The element could not be serialized.
+    Write(xmltree::Error),
+    /// The selected element was not found in the document.
+    NotFound,
+}
+
+impl From<xmltree::Error> for Error {
+    fn from(e: xmltree::Error) -> Self {
+        Error::Write(e)
+    }
+}
+
+impl From<xml::reader::Error> for Error {
+    fn from(e: xml::reader::Error) -> Self {
+        Error::Xml(e)
+    }
+}
+
+/// Part of the document to canonicalize.
+#[derive(Clone, Copy, Debug)]
+pub enum Selector<'a> {
+    /// The whole document, including the comments and processing
+    /// instructions outside of the document element.
+    Document,
+    /// The root element of the document.
+    Root,
+    /// The first element having an `Id` attribute (in any namespace, like
+    /// `wsu:Id`) with the given value.
+    Id(&'a str),
+    /// The first element with the given namespace and local name.
+    Element {
+        namespace: &'a str,
+        local_name: &'a str,
+    },
+}
+
+impl<'a> Selector<'a> {
+    fn matches(&self, name: &OwnedName, attributes: &[OwnedAttribute]) -> bool {
+        match self {
+            Selector::Document | Selector::Root => true,
+            Selector::Id(id) => attributes
+                .iter()
+                .any(|a| a.name.local_name == "Id" && a.value == *id),
+            Selector::Element {
+                namespace,
+                local_name,
+            } => {
+                name.local_name == *local_name
+                    && name.namespace.as_deref().unwrap_or_default() == *namespace
+            }
+        }
+    }
+}
+
+/// Canonicalization algorithm.
+#[derive(Clone, Copy, Debug)]
+pub enum Algorithm<'a> {
+    /// Canonical XML 1.0: all the namespaces in scope are rendered on the
+    /// apex element, and `xml:*` attributes are inherited from its
+    /// ancestors.
+    Inclusive,
+    /// Exclusive XML Canonicalization 1.0: only visibly utilized namespaces
+    /// are rendered.
+    ///
+    /// `inclusive_prefixes` is the `InclusiveNamespaces PrefixList`: those
+    /// namespaces are rendered as with inclusive canonicalization. The
+    /// default namespace is designated by `#default`.
+    Exclusive { inclusive_prefixes: &'a [&'a str] },
+}
+
+/// Canonicalize the selected element of `xml` with Exclusive XML
+/// Canonicalization 1.0, omitting comments.
+pub fn exclusive(
+    xml: &str,
+    selector: Selector,
+    inclusive_prefixes: &[&str],
+) -> Result<String, Error> {
+    canonicalize(
+        xml,
+        selector,
+        Algorithm::Exclusive { inclusive_prefixes },
+        false,
+    )
+}
+
+/// Canonicalize the selected element of `xml` with Canonical XML 1.0,
+/// omitting comments.
+pub fn inclusive(xml: &str, selector: Selector) -> Result<String, Error> {
+    canonicalize(xml, selector, Algorithm::Inclusive, false)
+}
+
+/// Canonicalize an element, as a whole document.
+pub fn element(
+    element: &Element,
+    algorithm: Algorithm,
+    with_comments: bool,
+) -> Result<String, Error> {
+    let mut xml = Vec::new();
+    element.write(&mut xml)?;
+    canonicalize(
+        &String::from_utf8_lossy(&xml),
+        Selector::Root,
+        algorithm,
+        with_comments,
+    )
+}
+
+/// Canonicalize the selected part of `xml`.
+pub fn canonicalize(
+    xml: &str,
+    selector: Selector,
+    algorithm: Algorithm,
+    with_comments: bool,
+) -> Result<String, Error> {
+    let mut config = ParserConfig::new();
+    config.trim_whitespace = false;
+    config.whitespace_to_characters = true;
+    config.cdata_to_characters = true;
+    config.coalesce_characters = true;
+    config.ignore_comments = !with_comments;
+
+    let inclusive_prefixes = match algorithm {
+        Algorithm::Inclusive => None,
+        Algorithm::Exclusive { inclusive_prefixes } => Some(
+            inclusive_prefixes
+                .iter()
+                .map(|p| if *p == "#default" { "" } else { *p }.to_string())
+                .collect::<BTreeSet<_>>(),
+        ),
+    };
+    let document = matches!(selector, Selector::Document);
+
+    let mut out = String::new();
+    // namespaces rendered by each output ancestor
+    let mut rendered: Vec<BTreeMap<String, String>> = Vec::new();
+    // `xml:*` attributes of the ancestors of the apex element
+    let mut ancestors: Vec<Vec<OwnedAttribute>> = Vec::new();
+    let mut after_root = false;
+
+    for event in config.create_reader(xml.as_bytes()) {
+        match event? {
+            XmlEvent::StartElement {
+                name,
+                mut attributes,
+                namespace,
+            } => {
+                if rendered.is_empty() && !selector.matches(&name, &attributes) {
+                    ancestors.push(
+                        attributes
+                            .into_iter()
+                            .filter(|a| a.name.prefix.as_deref() == Some("xml"))
+                            .collect(),
+                    );
+                    continue;
+                }
+
+                let parent = rendered.last().cloned().unwrap_or_default();
+                let mut current = parent.clone();
+
+                let declarations = match inclusive_prefixes.as_ref() {
+                    None => {
+                        if rendered.is_empty() {
+                            inherit_xml_attributes(&mut attributes, &ancestors);
+                        }
+                        let in_scope = namespace
+                            .iter()
+                            .map(|(prefix, _)| prefix.to_string())
+                            .collect();
+                        namespace_declarations(&in_scope, &namespace, &parent)
+                    }
+                    Some(inclusive_prefixes) => {
+                        let mut utilized = BTreeSet::new();
+                        utilized.insert(name.prefix.clone().unwrap_or_default());
+                        for attribute in attributes.iter() {
+                            if let Some(prefix) = attribute.name.prefix.as_ref() {
+                                utilized.insert(prefix.clone());
+                            }
+                        }
+                        for prefix in inclusive_prefixes.iter() {
+                            if namespace.get(prefix).is_some() {
+                                utilized.insert(prefix.clone());
+                            }
+                        }
+                        namespace_declarations(&utilized, &namespace, &parent)
+                    }
+                };
+
+                out.push('<');
+                out.push_str(&qualified_name(&name));
+                for (prefix, uri) in declarations {
+                    if prefix.is_empty() {
+                        out.push_str(" xmlns=\"");
+                    } else {
+                        out.push_str(" xmlns:");
+                        out.push_str(&prefix);
+                        out.push_str("=\"");
+                    }
+                    escape_attribute(&uri, &mut out);
+                    out.push('"');
+                    current.insert(prefix, uri);
+                }
+
+                attributes.sort_by(|a, b| {
+                    let a_ns = a.name.namespace.as_deref().unwrap_or_default();
+                    let b_ns = b.name.namespace.as_deref().unwrap_or_default();
+                    (a_ns, &a.name.local_name).cmp(&(b_ns, &b.name.local_name))
+                });
+                for attribute in attributes.iter() {
+                    out.push(' ');
+                    out.push_str(&qualified_name(&attribute.name));
+                    out.push_str("=\"");
+                    escape_attribute(&attribute.value, &mut out);
+                    out.push('"');
+                }
+                out.push('>');
+
+                rendered.push(current);
+            }
+            XmlEvent::EndElement { name } if !rendered.is_empty() => {
+                rendered.pop();
+                out.push_str("</");
+                out.push_str(&qualified_name(&name));
+                out.push('>');
+
+                if rendered.is_empty() {
+                    if !document {
+                        return Ok(out);
+                    }
+                    after_root = true;
+                }
+            }
+            XmlEvent::EndElement { .. } => {
+                ancestors.pop();
+            }
+            XmlEvent::Characters(text) if !rendered.is_empty() => {
+                escape_text(&text, &mut out);
+            }
+            XmlEvent::ProcessingInstruction { name, data } => {
+                let mut pi = format!("<?{}", name);
+                // the whitespace separating the target from the data is not
+                // part of the data
+                let data = data.as_deref().unwrap_or_default().trim_start();
+                if !data.is_empty() {
+                    pi.push(' ');
+                    pi.push_str(data);
+                }
+                pi.push_str("?>");
+                render_node(&pi, !rendered.is_empty(), document, after_root, &mut out);
+            }
+            XmlEvent::Comment(text) => {
+                let comment = format!("<!--{}-->", text);
+                render_node(
+                    &comment,
+                    !rendered.is_empty(),
+                    document,
+                    after_root,
+                    &mut out,
+                );
+            }
+            XmlEvent::EndDocument if document => return Ok(out),
+            _ => {}
+        }
+    }
+
+    Err(Error::NotFound)
+}
+
+/// Render a comment or a processing instruction. Outside of the document
+/// element, they are separated from it by a line break.
+fn render_node(node: &str, in_element: bool, document: bool, after_root: bool, out: &mut String) {
+    if in_element {
+        out.push_str(node);
+    } else if document && after_root {
+        out.push('\n');
+        out.push_str(node);
+    } else if document {
+        out.push_str(node);
+        out.push('\n');
+    }
+}
+
+/// Add the `xml:*` attributes of the ancestors of the apex element that it
+/// does not override.
+fn inherit_xml_attributes(attributes: &mut Vec<OwnedAttribute>, ancestors: &[Vec<OwnedAttribute>]) {
+    for ancestor in ancestors.iter().rev() {
+        for attribute in ancestor.iter() {
+            if !attributes.iter().any(|a| {
+                a.name.prefix == attribute.name.prefix
+                    && a.name.local_name == attribute.name.local_name
+            }) {
+                attributes.push(attribute.clone());
+            }
+        }
+    }
+}
+
+/// Namespace declarations to render for an element, sorted by prefix.
+fn namespace_declarations(
+    utilized: &BTreeSet<String>,
+    namespace: &Namespace,
+    parent: &BTreeMap<String, String>,
+) -> Vec<(String, String)> {
+    let mut declarations = Vec::new();
+
+    for prefix in utilized.iter() {
+        if prefix == "xml" || prefix == "xmlns" {
+            continue;
+        }
+        let uri = namespace.get(prefix).unwrap_or_default();
+
+        if prefix.is_empty() {
+            // the default namespace is only undeclared if an output
+            // ancestor declared it
+            let parent_uri = parent.get("").map(|s| s.as_str()).unwrap_or_default();
+            if uri == parent_uri {
+                continue;
+            }
+        } else if uri.is_empty() || parent.get(prefix).map(|s| s.as_str()) == Some(uri) {
+            continue;
+        }
+
+        declarations.push((prefix.clone(), uri.to_string()));
+    }
+
+    declarations
+}
+
+fn qualified_name(name: &OwnedName) -> String {
+    match name.prefix.as_ref() {
+        Some(prefix) => format!("{}:{}", prefix, name.local_name),
+        None => name.local_name.clone(),
+    }
+}
+
+fn escape_text(text: &str, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '\r' => out.push_str("&#xD;"),
+            c => out.push(c),
+        }
+    }
+}
+
+fn escape_attribute(text: &str, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '"' => out.push_str("&quot;"),
+            '\t' => out.push_str("&#x9;"),
+            '\n' => out.push_str("&#xA;"),
+            '\r' => out.push_str("&#xD;"),
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpser::xml::BuildElement;
+
+    #[test]
+    fn exclusive_subtree() {
+        // example from section 2.2 of the Exclusive XML Canonicalization spec
+        let doc = r#"<n0:local xmlns:n0="foo:bar" xmlns:n3="ftp://example.org"><n1:elem2 xmlns:n1="http://example.net" xml:lang="en"><n3:stuff xmlns:n3="ftp://example.org"/></n1:elem2></n0:local>"#;
+
+        assert_eq!(
+            exclusive(
+                doc,
+                Selector::Element {
+                    namespace: "http://example.net",
+                    local_name: "elem2"
+                },
+                &[]
+            )
+            .unwrap(),
+            r#"<n1:elem2 xmlns:n1="http://example.net" xml:lang="en"><n3:stuff xmlns:n3="ftp://example.org"></n3:stuff></n1:elem2>"#
+        );
+    }
+
+    #[test]
+    fn select_by_id() {
+        let doc = r#"<?xml version="1.0"?>
+<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/" xmlns:unused="urn:unused">
+  <soap:Body xmlns:wsu="urn:wsu" wsu:Id="body" b="2" a="1">
+    <ns:Echo xmlns:ns="urn:test"><value attr="a&#9;b">x &amp; y &gt; z</value><empty/></ns:Echo>
+  </soap:Body>
+</soap:Envelope>"#;
+
+        assert_eq!(
+            exclusive(doc, Selector::Id("body"), &[]).unwrap(),
+            concat!(
+                r#"<soap:Body xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/" xmlns:wsu="urn:wsu" a="1" b="2" wsu:Id="body">"#,
+                "\n    ",
+                r#"<ns:Echo xmlns:ns="urn:test"><value attr="a&#x9;b">x &amp; y &gt; z</value><empty></empty></ns:Echo>"#,
+                "\n  </soap:Body>"
+            )
+        );
+    }
+
+    #[test]
+    fn inclusive_prefixes() {
+        let doc = r#"<a xmlns="urn:default" xmlns:p="urn:p"><b/></a>"#;
+
+        assert_eq!(
+            exclusive(doc, Selector::Root, &["p"]).unwrap(),
+            r#"<a xmlns="urn:default" xmlns:p="urn:p"><b></b></a>"#
+        );
+        assert!(matches!(
+            exclusive(doc, Selector::Id("missing"), &[]),
+            Err(Error::NotFound)
+        ));
+    }
+
+    // test vectors from section 3 of the Canonical XML 1.0 spec, and section
+    // 2.2 of the Exclusive XML Canonicalization spec
+
+    const PI_AND_COMMENTS: &str = r#"<?xml version="1.0"?>
+
+<?xml-stylesheet   href="doc.xsl"
+   type="text/xsl"   ?>
+
+<!DOCTYPE doc SYSTEM "doc.dtd">
+
+<doc>Hello, world!<!-- Comment 1 --></doc>
+
+<?pi-without-data     ?>
+
+<!-- Comment 2 -->
+
+<!-- Comment 3 -->"#;
+
+    #[test]
+    fn w3c_pis_and_comments() {
+        assert_eq!(
+            canonicalize(
+                PI_AND_COMMENTS,
+                Selector::Document,
+                Algorithm::Inclusive,
+                false
+            )
+            .unwrap(),
+            r#"<?xml-stylesheet href="doc.xsl"
+   type="text/xsl"   ?>
+<doc>Hello, world!</doc>
+<?pi-without-data?>"#
+        );
+    }
+
+    #[test]
+    fn w3c_pis_and_comments_with_comments() {
+        assert_eq!(
+            canonicalize(
+                PI_AND_COMMENTS,
+                Selector::Document,
+                Algorithm::Inclusive,
+                true
+            )
+            .unwrap(),
+            r#"<?xml-stylesheet href="doc.xsl"
+   type="text/xsl"   ?>
+<doc>Hello, world!<!-- Comment 1 --></doc>
+<?pi-without-data?>
+<!-- Comment 2 -->
+<!-- Comment 3 -->"#
+        );
+    }
+
+    #[test]
+    fn w3c_whitespace_in_content() {
+        let doc = r#"<doc>
+   <clean>   </clean>
+   <dirty>   A   B   </dirty>
+   <mixed>
+      A
+      <clean>   </clean>
+      B
+      <dirty>   A   B   </dirty>
+      C
+   </mixed>
+</doc>"#;
+
+        assert_eq!(inclusive(doc, Selector::Document).unwrap(), doc);
+    }
+
+    #[test]
+    fn w3c_start_and_end_tags() {
+        // without the DTD, and so without the default attribute of `e9`
+        let doc = r#"<doc>
+   <e1   />
+   <e2   ></e2>
+   <e3   name = "elem3"   id="elem3"   />
+   <e4   name="elem4"   id="elem4"   ></e4>
+   <e5 a:attr="out" b:attr="sorted" attr2="all" attr="I'm"
+      xmlns:b="http://www.ietf.org"
+      xmlns:a="http://www.w3.org"
+      xmlns="http://example.org"/>
+   <e6 xmlns="" xmlns:a="http://www.w3.org">
+      <e7 xmlns="http://www.ietf.org">
+         <e8 xmlns="" xmlns:a="http://www.w3.org">
+            <e9 xmlns="" xmlns:a="http://www.ietf.org"/>
+         </e8>
+      </e7>
+   </e6>
+</doc>"#;
+
+        assert_eq!(
+            inclusive(doc, Selector::Document).unwrap(),
+            r#"<doc>
+   <e1></e1>
+   <e2></e2>
+   <e3 id="elem3" name="elem3"></e3>
+   <e4 id="elem4" name="elem4"></e4>
+   <e5 xmlns="http://example.org" xmlns:a="http://www.w3.org" xmlns:b="http://www.ietf.org" attr="I'm" attr2="all" b:attr="sorted" a:attr="out"></e5>
+   <e6 xmlns:a="http://www.w3.org">
+      <e7 xmlns="http://www.ietf.org">
+         <e8 xmlns="">
+            <e9 xmlns:a="http://www.ietf.org"></e9>
+         </e8>
+      </e7>
+   </e6>
+</doc>"#
+        );
+    }
+
+    #[test]
+    fn w3c_character_modifications() {
+        // without the DTD, and so without the `normNames` and `normId`
+        // elements, normalized according to their declared types
+        let doc = r#"<doc>
+   <text>First line&#x0d;&#10;Second line</text>
+   <value>&#x32;</value>
+   <compute><![CDATA[value>"0" && value<"10" ?"valid":"error"]]></compute>
+   <compute expr='value>"0" &amp;&amp; value&lt;"10" ?"valid":"error"'>valid</compute>
+   <norm attr=' &apos;   &#x20;&#13;&#xa;&#9;   &apos; '/>
+</doc>"#;
+
+        assert_eq!(
+            inclusive(doc, Selector::Document).unwrap(),
+            "<doc>
+   <text>First line&#xD;
+Second line</text>
+   <value>2</value>
+   <compute>value&gt;\"0\" &amp;&amp; value&lt;\"10\" ?\"valid\":\"error\"</compute>
+   <compute expr=\"value>&quot;0&quot; &amp;&amp; value&lt;&quot;10&quot; ?&quot;valid&quot;:&quot;error&quot;\">valid</compute>
+   <norm attr=\" '    &#xD;&#xA;&#x9;   ' \"></norm>
+</doc>"
+        );
+    }
+
+    #[test]
+    fn w3c_utf8() {
+        let doc = "<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>\n<doc>&#169;</doc>";
+
+        assert_eq!(
+            inclusive(doc, Selector::Document).unwrap(),
+            "<doc>\u{a9}</doc>"
+        );
+    }
+
+    #[test]
+    fn w3c_exclusive_vs_inclusive() {
+        let first = r#"<n0:local xmlns:n0="foo:bar" xmlns:n3="ftp://example.org"><n1:elem2 xmlns:n1="http://example.net" xml:lang="en"><n3:stuff xmlns:n3="ftp://example.org"/></n1:elem2></n0:local>"#;
+        let second = r#"<n2:pdu xmlns:n1="http://example.com" xmlns:n2="http://foo.example" xml:lang="fr" xml:space="retain"><n1:elem2 xmlns:n1="http://example.net" xml:lang="en"><n3:stuff xmlns:n3="ftp://example.org"/></n1:elem2></n2:pdu>"#;
+        let elem2 = Selector::Element {
+            namespace: "http://example.net",
+            local_name: "elem2",
+        };
+
+        assert_eq!(
+            inclusive(first, elem2).unwrap(),
+            r#"<n1:elem2 xmlns:n0="foo:bar" xmlns:n1="http://example.net" xmlns:n3="ftp://example.org" xml:lang="en"><n3:stuff></n3:stuff></n1:elem2>"#
+        );
+        assert_eq!(
+            inclusive(second, elem2).unwrap(),
+            r#"<n1:elem2 xmlns:n1="http://example.net" xmlns:n2="http://foo.example" xml:lang="en" xml:space="retain"><n3:stuff xmlns:n3="ftp://example.org"></n3:stuff></n1:elem2>"#
+        );
+        assert_eq!(
+            exclusive(first, elem2, &[]).unwrap(),
+            exclusive(second, elem2, &[]).unwrap()
+        );
+    }
+
+    #[test]
+    fn canonicalize_element() {
+        let element = Element::node("ns:b")
+            .with_attr("xmlns:ns", "urn:b")
+            .with_attr("z", "1")
+            .with_attr("a", "<\"2\">")
+            .with_child(Element::node("c").with_text("x > y"));
+
+        assert_eq!(
+            super::element(&element, Algorithm::Inclusive, false).unwrap(),
+            r#"<ns:b xmlns:ns="urn:b" a="&lt;&quot;2&quot;>" z="1"><c>x &gt; y</c></ns:b>"#
+        );
+    }
+}
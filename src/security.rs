@@ -0,0 +1,294 @@
+//! WS-Security headers.
+//!
+//! Implements the `UsernameToken` profile (with text or digest passwords) and
+//! `wsu:Timestamp`. A `Security` value is an `Interceptor`, so it can be
+//! attached to any generated client:
+//!
+//! ```rust,ignore
+//! let client = soap::StockQuoteService::new(url).with_interceptor(
+//!     Security::new()
+//!         .with_timestamp(chrono::Duration::minutes(5))
+//!         .with_username_token(UsernameToken::digest("user", "password")),
+//! );
+//! ```
+//!
+//! XML signatures with X.509 certificates are available in the `signature`
+//! module, behind the `signature` feature.
+
+#[cfg(feature = "signature")]
+pub mod signature;
+
+use crate::interceptor::{Interceptor, RequestContext};
+use crate::rpser::xml::BuildElement;
+use chrono::{DateTime, Duration, Utc};
+use sha1::{Digest, Sha1};
+use std::fmt;
+use xmltree::{Element, XMLNode};
+
+pub const WSSE_NS: &str =
+    "http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-wssecurity-secext-1.0.xsd";
+pub const WSU_NS: &str =
+    "http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-wssecurity-utility-1.0.xsd";
+pub const PASSWORD_TEXT: &str =
+    "http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-username-token-profile-1.0#PasswordText";
+pub const PASSWORD_DIGEST: &str =
+    "http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-username-token-profile-1.0#PasswordDigest";
+pub const BASE64_BINARY: &str =
+    "http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-soap-message-security-1.0#Base64Binary";
+
+/// Format a date as expected in WS-Security headers.
+pub fn format_datetime(date: &DateTime<Utc>) -> String {
+    date.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+}
+
+/// Compute a `PasswordDigest`: `Base64(SHA-1(nonce + created + password))`.
+pub fn password_digest(nonce: &[u8], created: &str, password: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(nonce);
+    hasher.update(created.as_bytes());
+    hasher.update(password.as_bytes());
+    base64::encode(hasher.finalize())
+}
+
+/// How the password is sent in a `UsernameToken`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PasswordType {
+    /// The password is sent in clear text.
+    Text,
+    /// A digest of the password, a nonce and the creation time is sent.
+    Digest,
+}
+
+/// `wsse:UsernameToken` credentials.
+///
+/// The password is redacted from the `Debug` output.
+#[derive(Clone)]
+pub struct UsernameToken {
+    pub username: String,
+    pub password: String,
+    pub password_type: PasswordType,
+}
+
+impl UsernameToken {
+    /// Token sending the password in clear text (`PasswordText`).
+    pub fn text<U: Into<String>, P: Into<String>>(username: U, password: P) -> Self {
+        UsernameToken {
+            username: username.into(),
+            password: password.into(),
+            password_type: PasswordType::Text,
+        }
+    }
+
+    /// Token sending a password digest (`PasswordDigest`).
+    pub fn digest<U: Into<String>, P: Into<String>>(username: U, password: P) -> Self {
+        UsernameToken {
+            username: username.into(),
+            password: password.into(),
+            password_type: PasswordType::Digest,
+        }
+    }
+
+    /// Build the token element with a random nonce, created now.
+    pub fn to_element(&self) -> Element {
+        let nonce: [u8; 16] = rand::random();
+        self.to_element_with(&nonce, &Utc::now())
+    }
+
+    /// Build the token element with the given nonce and creation time.
+    pub fn to_element_with(&self, nonce: &[u8], created: &DateTime<Utc>) -> Element {
+        let created = format_datetime(created);
+        let password = match self.password_type {
+            PasswordType::Text => Element::node("wsse:Password")
+                .with_attr("Type", PASSWORD_TEXT)
+                .with_text(self.password.clone()),
+            PasswordType::Digest => Element::node("wsse:Password")
+                .with_attr("Type", PASSWORD_DIGEST)
+                .with_text(password_digest(nonce, &created, &self.password)),
+        };
+
+        Element::node("wsse:UsernameToken").with_children(vec![
+            Element::node("wsse:Username").with_text(self.username.clone()),
+            password,
+            Element::node("wsse:Nonce")
+                .with_attr("EncodingType", BASE64_BINARY)
+                .with_text(base64::encode(nonce)),
+            Element::node("wsu:Created").with_text(created),
+        ])
+    }
+}
+
+impl fmt::Debug for UsernameToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UsernameToken")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .field("password_type", &self.password_type)
+            .finish()
+    }
+}
+
+/// Build a `wsu:Timestamp` element valid from `created` for `ttl`.
+pub fn timestamp_element(id: &str, created: &DateTime<Utc>, ttl: Duration) -> Element {
+    Element::node("wsu:Timestamp")
+        .with_attr("wsu:Id", id)
+        .with_children(vec![
+            Element::node("wsu:Created").with_text(format_datetime(created)),
+            Element::node("wsu:Expires").with_text(format_datetime(&(*created + ttl))),
+        ])
+}
+
+/// Get the `wsse:Security` header of an envelope built by `rpser::Method`,
+/// creating it (and the `soap:Header`) if needed.
+pub fn security_header(envelope: &mut Element) -> &mut Element {
+    if envelope.get_child("soap:Header").is_none() {
+        envelope
+            .children
+            .insert(0, XMLNode::Element(Element::node("soap:Header")));
+    }
+    let header = envelope.get_mut_child("soap:Header").unwrap();
+
+    if header.get_child("wsse:Security").is_none() {
+        header.children.push(XMLNode::Element(
+            Element::node("wsse:Security")
+                .with_attr("xmlns:wsse", WSSE_NS)
+                .with_attr("xmlns:wsu", WSU_NS)
+                .with_attr("soap:mustUnderstand", "1"),
+        ));
+    }
+    header.get_mut_child("wsse:Security").unwrap()
+}
+
+/// WS-Security header interceptor.
+#[derive(Clone, Debug, Default)]
+pub struct Security {
+    pub username_token: Option<UsernameToken>,
+    /// Validity of the `wsu:Timestamp`, no timestamp is sent if `None`.
+    pub timestamp: Option<Duration>,
+}
+
+impl Security {
+    pub fn new() -> Self {
+        Security::default()
+    }
+
+    pub fn with_username_token(mut self, token: UsernameToken) -> Self {
+        self.username_token = Some(token);
+        self
+    }
+
+    pub fn with_timestamp(mut self, ttl: Duration) -> Self {
+        self.timestamp = Some(ttl);
+        self
+    }
+
+    /// Add the configured headers to the envelope.
+    pub fn apply(&self, envelope: &mut Element) {
+        let now = Utc::now();
+        let security = security_header(envelope);
+
+        if let Some(ttl) = self.timestamp {
+            security
+                .children
+                .push(XMLNode::Element(timestamp_element("TS-1", &now, ttl)));
+        }
+        if let Some(token) = self.username_token.as_ref() {
+            security.children.push(XMLNode::Element(token.to_element()));
+        }
+    }
+}
+
+impl Interceptor for Security {
+    fn before_send(
+        &self,
+        _context: &mut RequestContext,
+        envelope: &mut Element,
+    ) -> Result<(), crate::Error> {
+        self.apply(envelope);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpser::Method;
+
+    #[test]
+    fn digest() {
+        let nonce = base64::decode("LKqI6G/AikKCQrN0zqZFlg==").unwrap();
+        assert_eq!(
+            password_digest(&nonce, "2010-09-16T07:50:45Z", "userpassword"),
+            "tuOSpGlFlIXsozq4HFNeeGeFLEI="
+        );
+    }
+
+    #[test]
+    fn username_token_digest() {
+        let created: DateTime<Utc> = "2020-04-01T12:30:00Z".parse().unwrap();
+        let token =
+            UsernameToken::digest("alice", "secret").to_element_with(b"0123456789abcdef", &created);
+
+        assert_eq!(
+            token.to_string(),
+            format!(
+                concat!(
+                    r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+                    r#"<wsse:UsernameToken><wsse:Username>alice</wsse:Username>"#,
+                    r#"<wsse:Password Type="{}">{}</wsse:Password>"#,
+                    r#"<wsse:Nonce EncodingType="{}">MDEyMzQ1Njc4OWFiY2RlZg==</wsse:Nonce>"#,
+                    r#"<wsu:Created>2020-04-01T12:30:00.000Z</wsu:Created>"#,
+                    r#"</wsse:UsernameToken>"#
+                ),
+                PASSWORD_DIGEST,
+                password_digest(b"0123456789abcdef", "2020-04-01T12:30:00.000Z", "secret"),
+                BASE64_BINARY
+            )
+        );
+    }
+
+    #[test]
+    fn username_token_text() {
+        let created: DateTime<Utc> = "2020-04-01T12:30:00Z".parse().unwrap();
+        let token = UsernameToken::text("alice", "secret").to_element_with(b"nonce", &created);
+        let password = token.get_child("wsse:Password").unwrap();
+
+        assert_eq!(password.attributes.get("Type").unwrap(), PASSWORD_TEXT);
+        assert_eq!(password.get_text().unwrap(), "secret");
+    }
+
+    #[test]
+    fn redacted_password() {
+        let security = Security::new().with_username_token(UsernameToken::text("alice", "secret"));
+        let debug = format!("{:?}", security);
+
+        assert!(debug.contains("alice"));
+        assert!(!debug.contains("secret"));
+    }
+
+    #[test]
+    fn adds_security_header() {
+        let mut envelope = Method::new("Echo").as_element("urn:test");
+        Security::new()
+            .with_timestamp(Duration::minutes(5))
+            .with_username_token(UsernameToken::text("alice", "secret"))
+            .apply(&mut envelope);
+
+        let security = envelope
+            .get_child("soap:Header")
+            .and_then(|h| h.get_child("wsse:Security"))
+            .unwrap();
+        assert_eq!(security.attributes.get("xmlns:wsse").unwrap(), WSSE_NS);
+        assert_eq!(security.attributes.get("soap:mustUnderstand").unwrap(), "1");
+
+        let children: Vec<_> = security
+            .children
+            .iter()
+            .filter_map(|c| c.as_element())
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(children, vec!["wsu:Timestamp", "wsse:UsernameToken"]);
+
+        // the resulting envelope is valid XML
+        Element::parse(envelope.to_string().as_bytes()).unwrap();
+    }
+}
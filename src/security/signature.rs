@@ -0,0 +1,725 @@
+//! XML signature of outgoing messages and verification of responses, with
+//! the X.509 token profile.
+//!
+//! The `soap:Body` and the `wsu:Timestamp` are signed with RSA-SHA256 after
+//! exclusive canonicalization, and the signing certificate is sent in a
+//! `wsse:BinarySecurityToken`:
+//!
+//! ```rust,ignore
+//! let client = soap::StockQuoteService::new(url)
+//!     .with_interceptor(Signer::from_pem(&key_pem, &cert_pem)?)
+//!     .with_interceptor(Verifier::from_pem(&partner_cert_pem)?);
+//! ```
+//!
+//! Verification works on the raw response text, as parsing it into an
+//! `Element` loses information needed for canonicalization. Responses must
+//! have a signed `wsu:Timestamp`, valid at the time of verification, so that
+//! they cannot be replayed later.
+
+use super::{security_header, timestamp_element, BASE64_BINARY, WSU_NS};
+use crate::interceptor::{Interceptor, RequestContext};
+use crate::rpser::xml::c14n::{self, Selector};
+use crate::rpser::xml::BuildElement;
+use crate::transport::HttpResponse;
+use chrono::{DateTime, Duration, Utc};
+use openssl::hash::{hash, MessageDigest};
+use openssl::pkey::{PKey, Private};
+use openssl::stack::Stack;
+use openssl::x509::store::X509StoreBuilder;
+use openssl::x509::verify::X509VerifyFlags;
+use openssl::x509::{X509StoreContext, X509};
+use std::collections::HashSet;
+use xmltree::{Element, XMLNode};
+
+pub const DSIG_NS: &str = "http://www.w3.org/2000/09/xmldsig#";
+pub const EXC_C14N: &str = "http://www.w3.org/2001/10/xml-exc-c14n#";
+pub const RSA_SHA256: &str = "http://www.w3.org/2001/04/xmldsig-more#rsa-sha256";
+pub const SHA256: &str = "http://www.w3.org/2001/04/xmlenc#sha256";
+pub const X509V3: &str =
+    "http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-x509-token-profile-1.0#X509v3";
+
+const BODY_ID: &str = "id-body";
+const TIMESTAMP_ID: &str = "TS-1";
+const TOKEN_ID: &str = "X509-1";
+
+/// Signature creation or verification error.
+#[derive(Debug)]
+pub enum SignatureError {
+    Openssl(openssl::error::ErrorStack),
+    Canonicalization(c14n::Error),
+    Parse(xmltree::ParseError),
+    Base64(base64::DecodeError),
+    /// An element needed to check the signature is missing.
+    MissingElement(&'static str),
+    /// The signature uses an algorithm other than exclusive C14N,
+    /// RSA-SHA256 and SHA-256.
+    UnsupportedAlgorithm(String),
+    /// The digest of a referenced element does not match.
+    DigestMismatch {
+        reference: String,
+    },
+    /// The signature value does not match the signed info.
+    InvalidSignature,
+    /// The signing certificate is not trusted, or expired.
+    UntrustedCertificate,
+    /// The message body is not covered by the signature.
+    UnsignedBody,
+    /// The `wsu:Timestamp` is missing or not covered by the signature.
+    UnsignedTimestamp,
+    /// The `wsu:Timestamp` is expired, or created in the future.
+    ExpiredTimestamp,
+    /// Several elements use the same `Id`.
+    DuplicateId(String),
+}
+
+impl From<openssl::error::ErrorStack> for SignatureError {
+    fn from(e: openssl::error::ErrorStack) -> Self {
+        SignatureError::Openssl(e)
+    }
+}
+
+impl From<c14n::Error> for SignatureError {
+    fn from(e: c14n::Error) -> Self {
+        SignatureError::Canonicalization(e)
+    }
+}
+
+impl From<xmltree::ParseError> for SignatureError {
+    fn from(e: xmltree::ParseError) -> Self {
+        SignatureError::Parse(e)
+    }
+}
+
+impl From<base64::DecodeError> for SignatureError {
+    fn from(e: base64::DecodeError) -> Self {
+        SignatureError::Base64(e)
+    }
+}
+
+fn digest(xml: &str, id: &str, inclusive_prefixes: &[&str]) -> Result<String, SignatureError> {
+    let canonical = c14n::exclusive(xml, Selector::Id(id), inclusive_prefixes)?;
+    Ok(base64::encode(hash(
+        MessageDigest::sha256(),
+        canonical.as_bytes(),
+    )?))
+}
+
+fn canonical_signed_info(xml: &str, inclusive_prefixes: &[&str]) -> Result<String, SignatureError> {
+    Ok(c14n::exclusive(
+        xml,
+        Selector::Element {
+            namespace: DSIG_NS,
+            local_name: "SignedInfo",
+        },
+        inclusive_prefixes,
+    )?)
+}
+
+fn child_element_mut<'a>(parent: &'a mut Element, name: &str) -> Option<&'a mut Element> {
+    parent
+        .children
+        .iter_mut()
+        .filter_map(|c| c.as_mut_element())
+        .find(|c| c.name == name)
+}
+
+/// Signs outgoing envelopes.
+pub struct Signer {
+    key: PKey<Private>,
+    certificate: X509,
+    timestamp: Duration,
+}
+
+impl Signer {
+    /// Create a signer from an RSA private key and its certificate.
+    pub fn new(key: PKey<Private>, certificate: X509) -> Self {
+        Signer {
+            key,
+            certificate,
+            timestamp: Duration::minutes(5),
+        }
+    }
+
+    /// Create a signer from a PEM encoded private key and certificate.
+    pub fn from_pem(key: &[u8], certificate: &[u8]) -> Result<Self, SignatureError> {
+        Ok(Signer::new(
+            PKey::private_key_from_pem(key)?,
+            X509::from_pem(certificate)?,
+        ))
+    }
+
+    /// Validity of the `wsu:Timestamp` added if the envelope has none.
+    pub fn with_timestamp(mut self, ttl: Duration) -> Self {
+        self.timestamp = ttl;
+        self
+    }
+
+    /// Sign the body and the timestamp of an envelope built by
+    /// `rpser::Method`.
+    pub fn sign(&self, envelope: &mut Element) -> Result<(), SignatureError> {
+        let body = child_element_mut(envelope, "soap:Body")
+            .ok_or(SignatureError::MissingElement("Body"))?;
+        if !body.attributes.contains_key("wsu:Id") {
+            body.attributes
+                .insert("xmlns:wsu".to_string(), WSU_NS.to_string());
+            body.attributes
+                .insert("wsu:Id".to_string(), BODY_ID.to_string());
+        }
+        let body_id = body.attributes["wsu:Id"].clone();
+
+        let security = security_header(envelope);
+        let timestamp_id = match security.get_child("wsu:Timestamp") {
+            Some(timestamp) => timestamp
+                .attributes
+                .get("wsu:Id")
+                .cloned()
+                .ok_or(SignatureError::MissingElement("Timestamp Id"))?,
+            None => {
+                let timestamp = timestamp_element(TIMESTAMP_ID, &Utc::now(), self.timestamp);
+                security.children.insert(0, XMLNode::Element(timestamp));
+                TIMESTAMP_ID.to_string()
+            }
+        };
+
+        let token = Element::node("wsse:BinarySecurityToken")
+            .with_attr("EncodingType", BASE64_BINARY)
+            .with_attr("ValueType", X509V3)
+            .with_attr("wsu:Id", TOKEN_ID)
+            .with_text(base64::encode(self.certificate.to_der()?));
+        security.children.push(XMLNode::Element(token));
+
+        let xml = envelope.to_string();
+        let references = [body_id, timestamp_id]
+            .iter()
+            .map(|id| {
+                Ok(Element::node("ds:Reference")
+                    .with_attr("URI", format!("#{}", id))
+                    .with_children(vec![
+                        Element::node("ds:Transforms").with_child(
+                            Element::node("ds:Transform").with_attr("Algorithm", EXC_C14N),
+                        ),
+                        Element::node("ds:DigestMethod").with_attr("Algorithm", SHA256),
+                        Element::node("ds:DigestValue").with_text(digest(&xml, id, &[])?),
+                    ]))
+            })
+            .collect::<Result<Vec<_>, SignatureError>>()?;
+
+        let signature = Element::node("ds:Signature")
+            .with_attr("xmlns:ds", DSIG_NS)
+            .with_children(vec![
+                Element::node("ds:SignedInfo")
+                    .with_child(
+                        Element::node("ds:CanonicalizationMethod").with_attr("Algorithm", EXC_C14N),
+                    )
+                    .with_child(
+                        Element::node("ds:SignatureMethod").with_attr("Algorithm", RSA_SHA256),
+                    )
+                    .with_children(references),
+                Element::node("ds:SignatureValue"),
+                Element::node("ds:KeyInfo").with_child(
+                    Element::node("wsse:SecurityTokenReference").with_child(
+                        Element::node("wsse:Reference")
+                            .with_attr("URI", format!("#{}", TOKEN_ID))
+                            .with_attr("ValueType", X509V3),
+                    ),
+                ),
+            ]);
+        security_header(envelope)
+            .children
+            .push(XMLNode::Element(signature));
+
+        let signed_info = canonical_signed_info(&envelope.to_string(), &[])?;
+        let mut signer = openssl::sign::Signer::new(MessageDigest::sha256(), &self.key)?;
+        signer.update(signed_info.as_bytes())?;
+        let value = base64::encode(signer.sign_to_vec()?);
+
+        let signature = child_element_mut(security_header(envelope), "ds:Signature")
+            .ok_or(SignatureError::MissingElement("Signature"))?;
+        child_element_mut(signature, "ds:SignatureValue")
+            .ok_or(SignatureError::MissingElement("SignatureValue"))?
+            .children
+            .push(XMLNode::Text(value));
+
+        Ok(())
+    }
+}
+
+impl Interceptor for Signer {
+    fn before_send(
+        &self,
+        _context: &mut RequestContext,
+        envelope: &mut Element,
+    ) -> Result<(), crate::Error> {
+        self.sign(envelope)?;
+        Ok(())
+    }
+}
+
+/// Verifies the signature of responses.
+pub struct Verifier {
+    trusted: Vec<X509>,
+    /// Tolerated difference between the clocks of the parties.
+    clock_skew: Duration,
+}
+
+fn algorithm(element: &Element, name: &'static str) -> Result<String, SignatureError> {
+    element
+        .get_child(name)
+        .and_then(|e| e.attributes.get("Algorithm"))
+        .cloned()
+        .ok_or(SignatureError::MissingElement(name))
+}
+
+fn inclusive_prefixes(element: &Element) -> Vec<String> {
+    element
+        .get_child("InclusiveNamespaces")
+        .and_then(|e| e.attributes.get("PrefixList"))
+        .map(|list| list.split_whitespace().map(|s| s.to_string()).collect())
+        .unwrap_or_default()
+}
+
+fn text(element: &Element) -> String {
+    element
+        .get_text()
+        .map(|t| t.split_whitespace().collect())
+        .unwrap_or_default()
+}
+
+/// Collect the `Id` attributes, and the number of `SignedInfo` elements.
+fn collect_ids(
+    element: &Element,
+    ids: &mut HashSet<String>,
+    signed_infos: &mut usize,
+) -> Result<(), SignatureError> {
+    if let Some(id) = element.attributes.get("Id") {
+        if !ids.insert(id.clone()) {
+            return Err(SignatureError::DuplicateId(id.clone()));
+        }
+    }
+    if element.name == "SignedInfo" {
+        *signed_infos += 1;
+    }
+    for child in element.children.iter().filter_map(|c| c.as_element()) {
+        collect_ids(child, ids, signed_infos)?;
+    }
+    Ok(())
+}
+
+impl Verifier {
+    /// Accept signatures from this certificate, or from certificates it
+    /// issued.
+    pub fn new(trusted: X509) -> Self {
+        Verifier {
+            trusted: vec![trusted],
+            clock_skew: Duration::minutes(5),
+        }
+    }
+
+    /// Create a verifier from a PEM encoded certificate.
+    pub fn from_pem(trusted: &[u8]) -> Result<Self, SignatureError> {
+        Ok(Verifier::new(X509::from_pem(trusted)?))
+    }
+
+    /// Also accept signatures from this certificate.
+    pub fn with_trusted(mut self, trusted: X509) -> Self {
+        self.trusted.push(trusted);
+        self
+    }
+
+    /// Tolerated difference between the clocks of the parties when
+    /// checking the `wsu:Timestamp`, 5 minutes by default.
+    pub fn with_clock_skew(mut self, skew: Duration) -> Self {
+        self.clock_skew = skew;
+        self
+    }
+
+    /// Verify the certificate chain up to a trusted certificate, with the
+    /// validity dates, basic constraints and key usages.
+    fn is_trusted(&self, certificate: &X509) -> Result<bool, SignatureError> {
+        let mut store = X509StoreBuilder::new()?;
+        for trusted in self.trusted.iter() {
+            store.add_cert(trusted.clone())?;
+        }
+        // trusted certificates need not be self-signed
+        store.set_flags(X509VerifyFlags::PARTIAL_CHAIN)?;
+        let store = store.build();
+
+        let chain = Stack::new()?;
+        let mut context = X509StoreContext::new()?;
+        let trusted = context.init(&store, certificate, &chain, |c| {
+            let trusted = c.verify_cert()?;
+            if !trusted {
+                debug!("untrusted certificate: {}", c.error());
+            }
+            Ok(trusted)
+        })?;
+        Ok(trusted)
+    }
+
+    /// Check that the `wsu:Timestamp` is signed and currently valid.
+    fn check_timestamp(
+        &self,
+        security: &Element,
+        referenced: &HashSet<String>,
+    ) -> Result<(), SignatureError> {
+        let timestamp = security
+            .get_child("Timestamp")
+            .ok_or(SignatureError::UnsignedTimestamp)?;
+        if !timestamp
+            .attributes
+            .get("Id")
+            .is_some_and(|id| referenced.contains(id))
+        {
+            return Err(SignatureError::UnsignedTimestamp);
+        }
+
+        let date = |name: &'static str| {
+            timestamp
+                .get_child(name)
+                .map(text)
+                .ok_or(SignatureError::MissingElement(name))?
+                .parse::<DateTime<Utc>>()
+                .map_err(|_| SignatureError::ExpiredTimestamp)
+        };
+        let now = Utc::now();
+        if date("Created")? > now + self.clock_skew || date("Expires")? < now - self.clock_skew {
+            return Err(SignatureError::ExpiredTimestamp);
+        }
+        Ok(())
+    }
+
+    /// Find the certificate referenced by the `KeyInfo`, or use the
+    /// trusted certificate if there is none.
+    fn certificate(&self, security: &Element, signature: &Element) -> Result<X509, SignatureError> {
+        let reference = signature
+            .get_child("KeyInfo")
+            .and_then(|k| k.get_child("SecurityTokenReference"))
+            .and_then(|r| r.get_child("Reference"))
+            .and_then(|r| r.attributes.get("URI"));
+
+        match reference {
+            Some(uri) => {
+                let id = uri.trim_start_matches('#');
+                let token = security
+                    .children
+                    .iter()
+                    .filter_map(|c| c.as_element())
+                    .find(|e| {
+                        e.name == "BinarySecurityToken"
+                            && e.attributes.get("Id").map(|s| s.as_str()) == Some(id)
+                    })
+                    .ok_or(SignatureError::MissingElement("BinarySecurityToken"))?;
+
+                let certificate = X509::from_der(&base64::decode(text(token))?)?;
+                if self.is_trusted(&certificate)? {
+                    Ok(certificate)
+                } else {
+                    Err(SignatureError::UntrustedCertificate)
+                }
+            }
+            None if self.trusted.len() == 1 => Ok(self.trusted[0].clone()),
+            None => Err(SignatureError::MissingElement("KeyInfo")),
+        }
+    }
+
+    /// Verify the signature of a response envelope.
+    ///
+    /// The signature must cover the `soap:Body` and a valid `wsu:Timestamp`,
+    /// and all references must use exclusive canonicalization and SHA-256.
+    pub fn verify(&self, xml: &str) -> Result<(), SignatureError> {
+        let envelope = Element::parse(xml.as_bytes())?;
+
+        let mut ids = HashSet::new();
+        let mut signed_infos = 0;
+        collect_ids(&envelope, &mut ids, &mut signed_infos)?;
+        if signed_infos != 1 {
+            return Err(SignatureError::MissingElement("SignedInfo"));
+        }
+
+        let security = envelope
+            .get_child("Header")
+            .and_then(|h| h.get_child("Security"))
+            .ok_or(SignatureError::MissingElement("Security"))?;
+        let signature = security
+            .get_child("Signature")
+            .ok_or(SignatureError::MissingElement("Signature"))?;
+        let signed_info = signature
+            .get_child("SignedInfo")
+            .ok_or(SignatureError::MissingElement("SignedInfo"))?;
+
+        let c14n_method = algorithm(signed_info, "CanonicalizationMethod")?;
+        if c14n_method != EXC_C14N {
+            return Err(SignatureError::UnsupportedAlgorithm(c14n_method));
+        }
+        let signature_method = algorithm(signed_info, "SignatureMethod")?;
+        if signature_method != RSA_SHA256 {
+            return Err(SignatureError::UnsupportedAlgorithm(signature_method));
+        }
+
+        let mut referenced = HashSet::new();
+        for reference in signed_info
+            .children
+            .iter()
+            .filter_map(|c| c.as_element())
+            .filter(|c| c.name == "Reference")
+        {
+            let uri = reference
+                .attributes
+                .get("URI")
+                .ok_or(SignatureError::MissingElement("Reference URI"))?;
+            let id = uri.trim_start_matches('#');
+
+            let mut prefixes = Vec::new();
+            if let Some(transforms) = reference.get_child("Transforms") {
+                for transform in transforms.children.iter().filter_map(|c| c.as_element()) {
+                    match transform.attributes.get("Algorithm") {
+                        Some(a) if a == EXC_C14N => prefixes.extend(inclusive_prefixes(transform)),
+                        other => {
+                            return Err(SignatureError::UnsupportedAlgorithm(
+                                other.cloned().unwrap_or_default(),
+                            ))
+                        }
+                    }
+                }
+            }
+            let digest_method = algorithm(reference, "DigestMethod")?;
+            if digest_method != SHA256 {
+                return Err(SignatureError::UnsupportedAlgorithm(digest_method));
+            }
+
+            let expected = reference
+                .get_child("DigestValue")
+                .map(text)
+                .ok_or(SignatureError::MissingElement("DigestValue"))?;
+            let prefixes = prefixes.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+            if digest(xml, id, &prefixes)? != expected {
+                return Err(SignatureError::DigestMismatch {
+                    reference: id.to_string(),
+                });
+            }
+            referenced.insert(id.to_string());
+        }
+
+        let body_id = envelope
+            .get_child("Body")
+            .and_then(|b| b.attributes.get("Id"))
+            .ok_or(SignatureError::UnsignedBody)?;
+        if !referenced.contains(body_id) {
+            return Err(SignatureError::UnsignedBody);
+        }
+        self.check_timestamp(security, &referenced)?;
+
+        let certificate = self.certificate(security, signature)?;
+        let prefixes = signed_info
+            .get_child("CanonicalizationMethod")
+            .map(inclusive_prefixes)
+            .unwrap_or_default();
+        let prefixes = prefixes.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+        let canonical = canonical_signed_info(xml, &prefixes)?;
+
+        let value = signature
+            .get_child("SignatureValue")
+            .map(text)
+            .ok_or(SignatureError::MissingElement("SignatureValue"))?;
+        let public_key = certificate.public_key()?;
+        let mut verifier = openssl::sign::Verifier::new(MessageDigest::sha256(), &public_key)?;
+        verifier.update(canonical.as_bytes())?;
+        if verifier.verify(&base64::decode(value)?)? {
+            Ok(())
+        } else {
+            Err(SignatureError::InvalidSignature)
+        }
+    }
+}
+
+impl Interceptor for Verifier {
+    /// Verify successful responses. Faults are not checked, as they are
+    /// often not signed.
+    fn after_receive(
+        &self,
+        _context: &RequestContext,
+        response: &HttpResponse,
+    ) -> Result<(), crate::Error> {
+        if (200..300).contains(&response.status) {
+            self.verify(&String::from_utf8_lossy(&response.body))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpser::Method;
+    use openssl::asn1::Asn1Time;
+    use openssl::bn::BigNum;
+    use openssl::rsa::Rsa;
+    use openssl::x509::extension::BasicConstraints;
+    use openssl::x509::{X509Builder, X509NameBuilder};
+
+    fn self_signed(common_name: &str) -> (PKey<Private>, X509) {
+        issued(common_name, None, false)
+    }
+
+    /// Certificate signed by `issuer`, or self-signed, and a CA if `ca`.
+    fn issued(
+        common_name: &str,
+        issuer: Option<(&PKey<Private>, &X509)>,
+        ca: bool,
+    ) -> (PKey<Private>, X509) {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", common_name).unwrap();
+        let name = name.build();
+
+        let mut builder = X509Builder::new().unwrap();
+        builder.set_version(2).unwrap();
+        builder
+            .set_serial_number(&BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap())
+            .unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder
+            .set_issuer_name(issuer.map(|(_, c)| c.subject_name()).unwrap_or(&name))
+            .unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder
+            .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        let mut constraints = BasicConstraints::new();
+        if ca {
+            constraints.ca();
+        }
+        builder
+            .append_extension(constraints.critical().build().unwrap())
+            .unwrap();
+        builder
+            .sign(
+                issuer.map(|(k, _)| k).unwrap_or(&key),
+                MessageDigest::sha256(),
+            )
+            .unwrap();
+
+        (key, builder.build())
+    }
+
+    fn signed_envelope(key: PKey<Private>, certificate: X509) -> String {
+        sign_with(Signer::new(key, certificate))
+    }
+
+    fn sign_with(signer: Signer) -> String {
+        let mut envelope = Method::new("Echo")
+            .with(Element::node("value").with_text("ping"))
+            .as_element("urn:test");
+        signer.sign(&mut envelope).unwrap();
+        envelope.to_string()
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let (key, certificate) = self_signed("client");
+        let xml = signed_envelope(key, certificate.clone());
+
+        assert!(xml.contains("<wsse:BinarySecurityToken"));
+        assert!(xml.contains(r##"<ds:Reference URI="#id-body">"##));
+        assert!(xml.contains(r##"<ds:Reference URI="#TS-1">"##));
+        Verifier::new(certificate).verify(&xml).unwrap();
+    }
+
+    #[test]
+    fn tampered_body() {
+        let (key, certificate) = self_signed("client");
+        let xml = signed_envelope(key, certificate.clone()).replace(">ping<", ">pong<");
+
+        match Verifier::new(certificate).verify(&xml) {
+            Err(SignatureError::DigestMismatch { reference }) => assert_eq!(reference, "id-body"),
+            other => panic!("expected a digest mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn tampered_signature() {
+        let (key, certificate) = self_signed("client");
+        let xml = signed_envelope(key, certificate.clone()).replace(
+            r#"<ds:SignatureMethod Algorithm="http://www.w3.org/2001/04/xmldsig-more#rsa-sha256" />"#,
+            r#"<ds:SignatureMethod Algorithm="http://www.w3.org/2001/04/xmldsig-more#rsa-sha256"></ds:SignatureMethod>"#,
+        );
+        // equivalent serializations have the same canonical form
+        Verifier::new(certificate.clone()).verify(&xml).unwrap();
+
+        let xml = xml.replace("#TS-1\"", "#TS-1\" Id=\"ref\"");
+        assert!(matches!(
+            Verifier::new(certificate).verify(&xml),
+            Err(SignatureError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn untrusted_certificate() {
+        let (key, certificate) = self_signed("client");
+        let (_, other) = self_signed("other");
+        let xml = signed_envelope(key, certificate);
+
+        assert!(matches!(
+            Verifier::new(other).verify(&xml),
+            Err(SignatureError::UntrustedCertificate)
+        ));
+    }
+
+    #[test]
+    fn issued_certificates() {
+        let (ca_key, ca) = issued("ca", None, true);
+        let (key, certificate) = issued("client", Some((&ca_key, &ca)), false);
+        Verifier::new(ca.clone())
+            .verify(&signed_envelope(key.clone(), certificate.clone()))
+            .unwrap();
+
+        // only CA certificates can issue certificates
+        let (other_key, other) = issued("other", Some((&key, &certificate)), false);
+        assert!(matches!(
+            Verifier::new(certificate).verify(&signed_envelope(other_key, other)),
+            Err(SignatureError::UntrustedCertificate)
+        ));
+    }
+
+    #[test]
+    fn timestamps() {
+        let (key, certificate) = self_signed("client");
+        let verifier = Verifier::new(certificate.clone());
+
+        // expired ten minutes ago
+        let xml = sign_with(Signer::new(key, certificate).with_timestamp(Duration::minutes(-10)));
+        assert!(matches!(
+            verifier.verify(&xml),
+            Err(SignatureError::ExpiredTimestamp)
+        ));
+        verifier
+            .with_clock_skew(Duration::minutes(15))
+            .verify(&xml)
+            .unwrap();
+
+        let (key, certificate) = self_signed("client");
+        let xml = signed_envelope(key, certificate.clone());
+        let start = xml.find(r##"<ds:Reference URI="#TS-1">"##).unwrap();
+        let end = start + xml[start..].find("</ds:Reference>").unwrap() + "</ds:Reference>".len();
+        let xml = [&xml[..start], &xml[end..]].concat();
+        assert!(matches!(
+            Verifier::new(certificate).verify(&xml),
+            Err(SignatureError::UnsignedTimestamp)
+        ));
+    }
+
+    #[test]
+    fn unsigned_body() {
+        let (_, certificate) = self_signed("client");
+        let xml = Method::new("Echo").as_xml("urn:test");
+
+        assert!(matches!(
+            Verifier::new(certificate).verify(&xml),
+            Err(SignatureError::MissingElement(_))
+        ));
+    }
+}
@@ -0,0 +1,651 @@
+//! Server side of the generated code.
+//!
+//! With `Generator::with_server`, the generator produces a trait named after the
+//! WSDL `portType`, with one method per operation, and a `{PortType}Server`
+//! wrapper implementing `Service`. A `Dispatcher` parses the incoming
+//! envelopes, routes them to the operations by `SOAPAction` or by body
+//! element, and serializes the outputs or the faults:
+//!
+//! ```rust,ignore
+//! struct Quotes;
+//!
+//! #[async_trait]
+//! impl soap::StockQuotePortType for Quotes {
+//!     async fn get_last_trade_price(
+//!         &self,
+//!         input: soap::GetLastTradePriceInput,
+//!     ) -> Result<soap::GetLastTradePriceOutput, Fault> {
+//!         // ...
+//!     }
+//! }
+//!
+//! let dispatcher = Dispatcher::new(soap::StockQuotePortTypeServer(Quotes));
+//! savon::server::hyper::serve(&addr, dispatcher).await?;
+//! ```
+//!
+//! The dispatcher can also serve the WSDL, see `documents`. The `hyper`
+//! adapter requires the `server` feature.
+
+pub mod documents;
+
+use self::documents::Documents;
+use crate::mime;
+use crate::mtom;
+use crate::rpser::xml::BuildElement;
+use crate::rpser::{Method, Response, RpcError};
+use crate::transport::{HttpRequest, HttpResponse};
+use async_trait::async_trait;
+use xmltree::Element;
+
+const SOAP_NS: &str = "http://schemas.xmlsoap.org/soap/envelope/";
+
+/// SOAP fault returned by an operation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fault {
+    pub fault_code: String,
+    pub fault_string: String,
+    pub detail: Option<Element>,
+}
+
+impl Fault {
+    pub fn new<C: Into<String>, S: Into<String>>(fault_code: C, fault_string: S) -> Self {
+        Fault {
+            fault_code: fault_code.into(),
+            fault_string: fault_string.into(),
+            detail: None,
+        }
+    }
+
+    /// Fault caused by the request, with the `soap:Client` code.
+    pub fn client<S: Into<String>>(fault_string: S) -> Self {
+        Fault::new("soap:Client", fault_string)
+    }
+
+    /// Fault caused by the server, with the `soap:Server` code.
+    pub fn server<S: Into<String>>(fault_string: S) -> Self {
+        Fault::new("soap:Server", fault_string)
+    }
+
+    pub fn with_detail(mut self, detail: Element) -> Self {
+        self.detail = Some(detail);
+        self
+    }
+
+    /// Build the `soap:Fault` element.
+    pub fn to_element(&self) -> Element {
+        let mut fault = Element::node("soap:Fault")
+            .with_child(Element::node("faultcode").with_text(self.fault_code.clone()))
+            .with_child(Element::node("faultstring").with_text(self.fault_string.clone()));
+        if let Some(detail) = self.detail.as_ref() {
+            fault = fault.with_child(Element::node("detail").with_child(detail.clone()));
+        }
+        fault
+    }
+}
+
+/// The details of the error are logged, not sent to the client.
+impl From<crate::Error> for Fault {
+    fn from(e: crate::Error) -> Self {
+        error!("internal error: {:?}", e);
+        Fault::server("internal error")
+    }
+}
+
+/// Operation of a `Service`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Operation {
+    /// Name of the operation, and of the element in the request body.
+    pub name: &'static str,
+    pub soap_action: Option<&'static str>,
+    /// One way operations are answered with an empty `202` response.
+    pub one_way: bool,
+}
+
+/// Untyped service, implemented by the generated `{PortType}Server`.
+#[async_trait]
+pub trait Service: Send + Sync {
+    /// Namespace of the response elements.
+    fn namespace(&self) -> &'static str;
+
+    fn operations(&self) -> &'static [Operation];
+
+    /// Deserialize the input message from the request body element, call
+    /// the operation and serialize its output message.
+    async fn call(&self, operation: &str, input: &Element) -> Result<Vec<Element>, Fault>;
+}
+
+/// Answers SOAP requests with a `Service`.
+pub struct Dispatcher<S> {
+    service: S,
+    documents: Option<Documents>,
+    endpoint: Option<String>,
+    trusted_proxy_headers: bool,
+}
+
+impl<S: Service> Dispatcher<S> {
+    pub fn new(service: S) -> Self {
+        Dispatcher {
+            service,
+            documents: None,
+            endpoint: None,
+            trusted_proxy_headers: false,
+        }
+    }
+
+    /// Serve the WSDL and its imports on `GET` requests.
+    pub fn with_documents(mut self, documents: Documents) -> Self {
+        self.documents = Some(documents);
+        self
+    }
+
+    /// Public URL of the service, used in the served documents.
+    pub fn with_endpoint<U: Into<String>>(mut self, endpoint: U) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// Build the URL used in the served documents from the `Host`,
+    /// `X-Forwarded-Host` and `X-Forwarded-Proto` headers of the request.
+    ///
+    /// Those headers are set by the client, only enable this behind a proxy
+    /// overwriting them.
+    pub fn with_trusted_proxy_headers(mut self) -> Self {
+        self.trusted_proxy_headers = true;
+        self
+    }
+
+    pub fn service(&self) -> &S {
+        &self.service
+    }
+
+    /// Find the operation of a request, by `SOAPAction` first, then by
+    /// body element.
+    pub fn route(&self, soap_action: Option<&str>, element: &str) -> Option<&'static Operation> {
+        let operations = self.service.operations();
+
+        soap_action
+            .and_then(|action| {
+                operations
+                    .iter()
+                    .find(|operation| operation.soap_action == Some(action))
+            })
+            .or_else(|| {
+                operations
+                    .iter()
+                    .find(|operation| operation.name == element)
+            })
+    }
+
+    /// Answer a request. Errors are returned as SOAP faults.
+    pub async fn dispatch(&self, request: &HttpRequest) -> HttpResponse {
+        match self.call(request).await {
+            Ok(Some(body)) => response(200, body),
+            Ok(None) => HttpResponse {
+                status: 202,
+                headers: vec![],
+                body: vec![],
+            },
+            Err(fault) => {
+                debug!("fault: {:?}", fault);
+                response(500, envelope(fault.to_element()))
+            }
+        }
+    }
+
+    /// Answer a `GET` request: `?wsdl` returns the WSDL and `?xsd={path}`
+    /// the imported documents, rewritten for the endpoint of the service.
+    ///
+    /// The endpoint is the one set with `with_endpoint`, or built from the
+    /// headers with `with_trusted_proxy_headers`. Otherwise, it is the path
+    /// of the request, without scheme and host.
+    pub fn get(&self, request: &HttpRequest) -> HttpResponse {
+        let (url, query) = match request.url.split_once('?') {
+            Some((url, query)) => (url, query),
+            None => (request.url.as_str(), ""),
+        };
+        // absolute URLs are only used by proxies
+        let path = match url.split_once("://") {
+            Some((_, rest)) => rest.find('/').map(|index| &rest[index..]).unwrap_or("/"),
+            None => url,
+        };
+        let endpoint = match self.endpoint.as_ref() {
+            Some(endpoint) => endpoint.clone(),
+            None if self.trusted_proxy_headers => {
+                let host = request
+                    .header("X-Forwarded-Host")
+                    .or_else(|| request.header("Host"))
+                    .unwrap_or("localhost");
+                let scheme = request.header("X-Forwarded-Proto").unwrap_or("http");
+                format!("{}://{}{}", scheme, host, path)
+            }
+            None => path.to_string(),
+        };
+
+        let document = self.documents.as_ref().and_then(|documents| {
+            if query.eq_ignore_ascii_case("wsdl") {
+                Some(documents.wsdl(&endpoint))
+            } else {
+                query
+                    .strip_prefix("xsd=")
+                    .and_then(|path| documents.import(path, &endpoint))
+            }
+        });
+
+        let (status, content_type, body) = match document {
+            Some(Ok(document)) => (200, "text/xml; charset=utf-8", document.into_bytes()),
+            Some(Err(e)) => {
+                error!("cannot serve {}: {:?}", request.url, e);
+                (500, "text/plain", b"invalid document".to_vec())
+            }
+            None => (404, "text/plain", b"not found".to_vec()),
+        };
+        HttpResponse {
+            status,
+            headers: vec![("Content-Type".to_string(), content_type.to_string())],
+            body,
+        }
+    }
+
+    async fn call(&self, request: &HttpRequest) -> Result<Option<Element>, Fault> {
+        let body = match request.header("Content-Type") {
+            Some(content_type) if mime::media_type(content_type) == "multipart/related" => {
+                mtom::decode_inline(content_type, &request.body)
+                    .map_err(|e| Fault::client(format!("invalid multipart request: {:?}", e)))?
+                    .0
+            }
+            _ => request.body.clone(),
+        };
+
+        let text = String::from_utf8_lossy(&body);
+        trace!("received: {}", text);
+        let envelope = Response::from_xml(&text).map_err(|e| match e {
+            RpcError::Fault { .. } => Fault::client("unexpected fault in the request"),
+            e => Fault::client(format!("invalid envelope: {:?}", e)),
+        })?;
+
+        let soap_action = request
+            .header("SOAPAction")
+            .map(|action| action.trim_matches('"'))
+            .filter(|action| !action.is_empty());
+        let operation = self
+            .route(soap_action, &envelope.body.name)
+            .ok_or_else(|| {
+                Fault::client(format!(
+                    "unknown operation {} (SOAPAction {:?})",
+                    envelope.body.name, soap_action
+                ))
+            })?;
+
+        let output = self.service.call(operation.name, &envelope.body).await?;
+        if operation.one_way {
+            return Ok(None);
+        }
+
+        let mut method = Method::new(&format!("{}Response", operation.name));
+        method.args = output;
+        let mut body = method.as_element(self.service.namespace());
+        // binary content is sent inline
+        mtom::strip_markers(&mut body);
+        Ok(Some(body))
+    }
+}
+
+fn envelope(body: Element) -> Element {
+    Element::node("soap:Envelope")
+        .with_attr("xmlns:soap", SOAP_NS)
+        .with_child(Element::node("soap:Body").with_child(body))
+}
+
+fn response(status: u16, envelope: Element) -> HttpResponse {
+    let body = envelope.to_string();
+    trace!("sending: {}", body);
+
+    HttpResponse {
+        status,
+        headers: vec![(
+            "Content-Type".to_string(),
+            "text/xml; charset=utf-8".to_string(),
+        )],
+        body: body.into_bytes(),
+    }
+}
+
+/// Adapter serving a `Dispatcher` with hyper.
+#[cfg(feature = "server")]
+pub mod hyper {
+    use super::{Dispatcher, Service};
+    use crate::transport::HttpRequest;
+    use ::hyper::service::{make_service_fn, service_fn};
+    use ::hyper::{Body, Method, Request, Response, Server, StatusCode};
+    use std::convert::Infallible;
+    use std::future::Future;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    /// Answer a hyper request.
+    pub async fn handle<S: Service>(
+        dispatcher: &Dispatcher<S>,
+        request: Request<Body>,
+    ) -> Result<Response<Body>, ::hyper::Error> {
+        let (parts, body) = request.into_parts();
+        if parts.method != Method::POST && parts.method != Method::GET {
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+            return Ok(response);
+        }
+
+        let request = HttpRequest {
+            url: parts.uri.to_string(),
+            headers: parts
+                .headers
+                .iter()
+                .filter_map(|(k, v)| Some((k.as_str().to_string(), v.to_str().ok()?.to_string())))
+                .collect(),
+            body: ::hyper::body::to_bytes(body).await?.to_vec(),
+            timeout: None,
+        };
+        let response = if parts.method == Method::GET {
+            dispatcher.get(&request)
+        } else {
+            dispatcher.dispatch(&request).await
+        };
+
+        let mut builder = Response::builder().status(response.status);
+        for (key, value) in response.headers.iter() {
+            builder = builder.header(key.as_str(), value.as_str());
+        }
+        Ok(builder
+            .body(Body::from(response.body))
+            .expect("invalid response header"))
+    }
+
+    /// Bind a server to `address`. Returns the bound address, useful when
+    /// binding to port 0, and the server future.
+    pub fn bind<S: Service + 'static>(
+        address: &SocketAddr,
+        dispatcher: Dispatcher<S>,
+    ) -> Result<(SocketAddr, impl Future<Output = Result<(), ::hyper::Error>>), ::hyper::Error>
+    {
+        let dispatcher = Arc::new(dispatcher);
+        let make_service = make_service_fn(move |_| {
+            let dispatcher = dispatcher.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let dispatcher = dispatcher.clone();
+                    async move { handle(&dispatcher, request).await }
+                }))
+            }
+        });
+
+        let server = Server::try_bind(address)?.serve(make_service);
+        Ok((server.local_addr(), server))
+    }
+
+    /// Serve requests on `address`.
+    pub async fn serve<S: Service + 'static>(
+        address: &SocketAddr,
+        dispatcher: Dispatcher<S>,
+    ) -> Result<(), ::hyper::Error> {
+        let (_, server) = bind(address, dispatcher)?;
+        server.await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hand written equivalent of a generated server.
+    struct Echo;
+
+    #[async_trait]
+    impl Service for Echo {
+        fn namespace(&self) -> &'static str {
+            "urn:test"
+        }
+
+        fn operations(&self) -> &'static [Operation] {
+            &[
+                Operation {
+                    name: "Echo",
+                    soap_action: Some("urn:test/Echo"),
+                    one_way: false,
+                },
+                Operation {
+                    name: "Notify",
+                    soap_action: None,
+                    one_way: true,
+                },
+                Operation {
+                    name: "Download",
+                    soap_action: None,
+                    one_way: false,
+                },
+            ]
+        }
+
+        async fn call(&self, operation: &str, input: &Element) -> Result<Vec<Element>, Fault> {
+            let value = input
+                .get_child("value")
+                .and_then(|v| v.get_text())
+                .ok_or_else(|| Fault::client("missing value"))?;
+
+            match operation {
+                "Echo" if value == "busy" => Err(Fault::new("soap:Server.Busy", "busy")),
+                "Echo" => Ok(vec![Element::node("value").with_text(value)]),
+                "Download" => Ok(vec![mtom::binary_element("data", value.as_bytes())]),
+                _ => Ok(vec![]),
+            }
+        }
+    }
+
+    fn request(operation: &str, soap_action: &str, value: &str) -> HttpRequest {
+        let envelope = Method::new(operation)
+            .with(Element::node("value").with_text(value))
+            .as_xml("urn:test");
+        HttpRequest::new("/", envelope.into_bytes())
+            .with_header("Content-Type", "text/xml")
+            .with_header("SOAPAction", format!("\"{}\"", soap_action))
+    }
+
+    fn fault(response: &HttpResponse) -> (String, String) {
+        match Response::from_xml(&String::from_utf8_lossy(&response.body)) {
+            Err(RpcError::Fault {
+                fault_code,
+                fault_string,
+                ..
+            }) => (fault_code, fault_string),
+            other => panic!("expected a fault, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn dispatch() {
+        let dispatcher = Dispatcher::new(Echo);
+
+        let response = dispatcher.dispatch(&request("Echo", "", "ping")).await;
+        assert_eq!(response.status, 200);
+        assert_eq!(
+            response.header("Content-Type"),
+            Some("text/xml; charset=utf-8")
+        );
+        let body = Response::from_xml(&String::from_utf8_lossy(&response.body))
+            .unwrap()
+            .body;
+        assert_eq!(body.name, "EchoResponse");
+        assert_eq!(body.namespace.as_deref(), Some("urn:test"));
+        assert_eq!(
+            body.get_child("value").unwrap().get_text(),
+            Some("ping".into())
+        );
+
+        // routed by SOAPAction
+        let response = dispatcher
+            .dispatch(&request("Other", "urn:test/Echo", "ping"))
+            .await;
+        assert_eq!(response.status, 200);
+
+        let response = dispatcher.dispatch(&request("Notify", "", "ping")).await;
+        assert_eq!(response.status, 202);
+        assert!(response.body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn binary_output() {
+        let response = Dispatcher::new(Echo)
+            .dispatch(&request("Download", "", "content"))
+            .await;
+        assert_eq!(response.status, 200);
+        let body = String::from_utf8_lossy(&response.body);
+        assert!(!body.contains("savon-binary"));
+
+        let data = Response::from_xml(&body)
+            .unwrap()
+            .body
+            .get_child("data")
+            .unwrap()
+            .as_binary();
+        assert_eq!(data, Ok(b"content".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn faults() {
+        let dispatcher = Dispatcher::new(Echo);
+
+        let response = dispatcher.dispatch(&request("Echo", "", "busy")).await;
+        assert_eq!(response.status, 500);
+        assert_eq!(fault(&response).0, "soap:Server.Busy");
+
+        let response = dispatcher.dispatch(&request("Unknown", "", "ping")).await;
+        assert_eq!(fault(&response).0, "soap:Client");
+        assert!(fault(&response).1.contains("unknown operation Unknown"));
+
+        let response = dispatcher
+            .dispatch(&HttpRequest::new("/", b"not xml".to_vec()))
+            .await;
+        assert_eq!(response.status, 500);
+        assert_eq!(fault(&response).0, "soap:Client");
+    }
+
+    #[test]
+    fn internal_errors() {
+        let fault = Fault::from(crate::Error::Transport("db password rejected".into()));
+        assert_eq!(fault, Fault::server("internal error"));
+    }
+
+    #[test]
+    fn documents() {
+        let wsdl = r#"<definitions xmlns:soap="http://schemas.xmlsoap.org/wsdl/soap/">
+  <types><schema><include schemaLocation="types.xsd"/></schema></types>
+  <service><port><soap:address location="http://internal/echo"/></port></service>
+</definitions>"#;
+        let documents = Documents::new(wsdl).with_import("types.xsd", "<schema/>");
+        let request = HttpRequest::new("/echo?wsdl", Vec::new())
+            .with_header("Host", "attacker.example.com")
+            .with_header("X-Forwarded-Proto", "https");
+
+        // the headers are ignored by default
+        let dispatcher = Dispatcher::new(Echo).with_documents(documents.clone());
+        let response = dispatcher.get(&request);
+        assert_eq!(response.status, 200);
+        let body = String::from_utf8_lossy(&response.body);
+        assert!(body.contains(r#"location="/echo""#));
+        assert!(body.contains(r#"schemaLocation="/echo?xsd=types.xsd""#));
+
+        let response = Dispatcher::new(Echo)
+            .with_documents(documents.clone())
+            .with_endpoint("https://example.com:8080/echo")
+            .get(&request);
+        let body = String::from_utf8_lossy(&response.body);
+        assert!(body.contains(r#"location="https://example.com:8080/echo""#));
+        assert!(body.contains(r#"schemaLocation="https://example.com:8080/echo?xsd=types.xsd""#));
+
+        let response = Dispatcher::new(Echo)
+            .with_documents(documents)
+            .with_trusted_proxy_headers()
+            .get(&request.with_header("X-Forwarded-Host", "example.com"));
+        let body = String::from_utf8_lossy(&response.body);
+        assert!(body.contains(r#"location="https://example.com/echo""#));
+
+        let response = dispatcher.get(&HttpRequest::new("/echo?xsd=types.xsd", Vec::new()));
+        assert_eq!(response.status, 200);
+        assert!(String::from_utf8_lossy(&response.body).contains("<schema"));
+
+        let response = dispatcher.get(&HttpRequest::new("/echo?xsd=other.xsd", Vec::new()));
+        assert_eq!(response.status, 404);
+        let response = Dispatcher::new(Echo).get(&HttpRequest::new("/echo?wsdl", Vec::new()));
+        assert_eq!(response.status, 404);
+    }
+
+    #[cfg(feature = "server")]
+    #[tokio::test]
+    async fn hyper_adapter() {
+        use crate::http::{request_response, Call, CallOptions};
+        use crate::interceptor::Chain;
+        use crate::transport::ReqwestTransport;
+
+        #[derive(Debug)]
+        struct Value(String);
+
+        impl crate::gen::ToElements for Value {
+            fn to_elements(&self) -> Vec<Element> {
+                vec![Element::node("value").with_text(self.0.clone())]
+            }
+        }
+
+        impl crate::gen::FromElement for Value {
+            fn from_element(element: &Element) -> Result<Self, crate::Error> {
+                let value = element.get_at_path(&["value"])?;
+                Ok(Value(value.get_text().unwrap_or_default().to_string()))
+            }
+        }
+
+        let wsdl = r#"<definitions xmlns:soap="http://schemas.xmlsoap.org/wsdl/soap/">
+  <service><port><soap:address location="http://internal/"/></port></service>
+</definitions>"#;
+        let dispatcher = Dispatcher::new(Echo)
+            .with_documents(Documents::new(wsdl))
+            .with_trusted_proxy_headers();
+        let (address, server) = hyper::bind(&"127.0.0.1:0".parse().unwrap(), dispatcher).unwrap();
+        tokio::spawn(server);
+
+        let body = reqwest::get(&format!("http://{}/?wsdl", address))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(body.contains(&format!(r#"location="http://{}/""#, address)));
+
+        let interceptors = Chain::new();
+        let url = format!("http://{}/", address);
+        let call = Call {
+            base_url: &url,
+            namespace: "urn:test",
+            port_type: "TestPortType",
+            operation: "Echo",
+            input: "EchoRequest",
+            soap_action: Some("urn:test/Echo"),
+            mtom: false,
+            retry: None,
+            interceptors: &interceptors,
+            options: &CallOptions::default(),
+        };
+        let transport = ReqwestTransport::default();
+
+        let res: Result<Value, ()> =
+            request_response(&transport, &call, &Value("ping".to_string()))
+                .await
+                .unwrap();
+        assert_eq!(res.unwrap().0, "ping");
+
+        let res: Result<Result<Value, ()>, _> =
+            request_response(&transport, &call, &Value("busy".to_string())).await;
+        match res {
+            Err(crate::Error::Rpc(RpcError::Fault { fault_code, .. })) => {
+                assert_eq!(fault_code, "soap:Server.Busy")
+            }
+            other => panic!("expected a fault, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,292 @@
+//! WSDL and XSD documents served by the `Dispatcher`.
+//!
+//! `GET {endpoint}?wsdl` returns the WSDL, with the `soap:address` locations
+//! replaced by the endpoint URL. Relative imports and includes are served
+//! as `GET {endpoint}?xsd={path}`, `path` being relative to the WSDL, and
+//! their locations are rewritten to those URLs.
+
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use xml::attribute::OwnedAttribute;
+use xml::reader::{ParserConfig, XmlEvent};
+use xml::writer::EmitterConfig;
+
+/// Document loading or rewriting error.
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Xml(xml::reader::Error),
+    Write(xml::writer::Error),
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<xml::reader::Error> for Error {
+    fn from(e: xml::reader::Error) -> Self {
+        Error::Xml(e)
+    }
+}
+
+impl From<xml::writer::Error> for Error {
+    fn from(e: xml::writer::Error) -> Self {
+        Error::Write(e)
+    }
+}
+
+/// WSDL and the schemas it imports.
+#[derive(Clone, Debug)]
+pub struct Documents {
+    wsdl: String,
+    /// Imported documents, by path relative to the WSDL.
+    imports: HashMap<String, String>,
+}
+
+impl Documents {
+    pub fn new<S: Into<String>>(wsdl: S) -> Self {
+        Documents {
+            wsdl: wsdl.into(),
+            imports: HashMap::new(),
+        }
+    }
+
+    /// Add an imported document, `path` being relative to the WSDL.
+    pub fn with_import<P: Into<String>, S: Into<String>>(mut self, path: P, document: S) -> Self {
+        self.imports.insert(path.into(), document.into());
+        self
+    }
+
+    /// Load a WSDL file, and the files it imports with relative locations,
+    /// recursively.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut documents = Documents::new(std::fs::read_to_string(path)?);
+
+        let mut pending = relative_imports(&documents.wsdl, "")?;
+        while let Some(import) = pending.pop() {
+            if documents.imports.contains_key(&import) {
+                continue;
+            }
+            let document = std::fs::read_to_string(directory.join(&import))?;
+            pending.extend(relative_imports(&document, &import)?);
+            documents.imports.insert(import, document);
+        }
+
+        Ok(documents)
+    }
+
+    /// Paths of the imported documents.
+    pub fn imports(&self) -> impl Iterator<Item = &str> {
+        self.imports.keys().map(|k| k.as_str())
+    }
+
+    /// The WSDL, with the addresses and the imports rewritten for the
+    /// given endpoint URL.
+    pub fn wsdl(&self, endpoint: &str) -> Result<String, Error> {
+        rewrite(&self.wsdl, "", endpoint)
+    }
+
+    /// An imported document, with its imports rewritten for the given
+    /// endpoint URL.
+    pub fn import(&self, path: &str, endpoint: &str) -> Option<Result<String, Error>> {
+        let document = self.imports.get(path)?;
+        Some(rewrite(document, path, endpoint))
+    }
+}
+
+fn is_relative(location: &str) -> bool {
+    !location.contains("://") && !location.starts_with('/')
+}
+
+/// Resolve a location relative to the document at `base`, both relative to
+/// the WSDL.
+fn resolve(base: &str, location: &str) -> String {
+    let mut path = PathBuf::from(base);
+    path.pop();
+
+    let mut components: Vec<String> = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    for component in Path::new(location).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if !components.is_empty() && components.last().unwrap() != ".." =>
+            {
+                components.pop();
+            }
+            c => components.push(c.as_os_str().to_string_lossy().into_owned()),
+        }
+    }
+    components.join("/")
+}
+
+/// Whether the attribute holds the location of an imported document.
+fn is_location(element: &str, attribute: &OwnedAttribute) -> bool {
+    matches!(
+        (element, attribute.name.local_name.as_str()),
+        // wsdl:import
+        ("import", "location")
+            // xsd:import, xsd:include and xsd:redefine
+            | ("import", "schemaLocation")
+            | ("include", "schemaLocation")
+            | ("redefine", "schemaLocation")
+    )
+}
+
+fn relative_imports(document: &str, base: &str) -> Result<Vec<String>, Error> {
+    let mut imports = Vec::new();
+
+    for event in ParserConfig::new().create_reader(document.as_bytes()) {
+        if let XmlEvent::StartElement {
+            name, attributes, ..
+        } = event?
+        {
+            imports.extend(
+                attributes
+                    .iter()
+                    .filter(|a| is_location(&name.local_name, a) && is_relative(&a.value))
+                    .map(|a| resolve(base, &a.value)),
+            );
+        }
+    }
+
+    Ok(imports)
+}
+
+/// Rewrite the addresses and relative imports of the document at `base`.
+fn rewrite(document: &str, base: &str, endpoint: &str) -> Result<String, Error> {
+    let mut config = ParserConfig::new();
+    config.trim_whitespace = false;
+    config.ignore_comments = false;
+
+    let mut output = Vec::new();
+    let mut writer = EmitterConfig::new().create_writer(&mut output);
+
+    for event in config.create_reader(document.as_bytes()) {
+        match event? {
+            XmlEvent::StartElement {
+                name,
+                mut attributes,
+                namespace,
+            } => {
+                for attribute in attributes.iter_mut() {
+                    if name.local_name == "address" && attribute.name.local_name == "location" {
+                        attribute.value = endpoint.to_string();
+                    } else if is_location(&name.local_name, attribute)
+                        && is_relative(&attribute.value)
+                    {
+                        attribute.value =
+                            format!("{}?xsd={}", endpoint, resolve(base, &attribute.value));
+                    }
+                }
+
+                let event = XmlEvent::StartElement {
+                    name,
+                    attributes,
+                    namespace,
+                };
+                writer.write(event.as_writer_event().unwrap())?;
+            }
+            event => {
+                if let Some(event) = event.as_writer_event() {
+                    writer.write(event)?;
+                }
+            }
+        }
+    }
+
+    Ok(String::from_utf8_lossy(&output).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xmltree::Element;
+
+    const WSDL: &str = r#"<?xml version="1.0"?>
+<definitions xmlns="http://schemas.xmlsoap.org/wsdl/"
+             xmlns:soap="http://schemas.xmlsoap.org/wsdl/soap/"
+             xmlns:xsd="http://www.w3.org/2001/XMLSchema">
+  <import namespace="urn:common" location="common.wsdl"/>
+  <types>
+    <xsd:schema targetNamespace="urn:quotes">
+      <xsd:import namespace="urn:types" schemaLocation="schemas/types.xsd"/>
+      <xsd:import namespace="urn:remote" schemaLocation="http://example.com/remote.xsd"/>
+    </xsd:schema>
+  </types>
+  <service name="Quotes">
+    <port name="QuotesPort" binding="tns:QuotesBinding">
+      <soap:address location="http://internal.example.com/quotes"/>
+    </port>
+  </service>
+</definitions>"#;
+
+    const TYPES: &str = r#"<xsd:schema xmlns:xsd="http://www.w3.org/2001/XMLSchema">
+  <xsd:include schemaLocation="../shared/./base.xsd"/>
+</xsd:schema>"#;
+
+    #[test]
+    fn resolve_paths() {
+        assert_eq!(resolve("", "types.xsd"), "types.xsd");
+        assert_eq!(resolve("schemas/types.xsd", "base.xsd"), "schemas/base.xsd");
+        assert_eq!(
+            resolve("schemas/types.xsd", "../shared/./base.xsd"),
+            "shared/base.xsd"
+        );
+        assert_eq!(resolve("", "../base.xsd"), "../base.xsd");
+    }
+
+    #[test]
+    fn rewrite_wsdl() {
+        let documents = Documents::new(WSDL).with_import("schemas/types.xsd", TYPES);
+        let endpoint = "http://localhost:8080/quotes";
+
+        let wsdl = documents.wsdl(endpoint).unwrap();
+        assert!(wsdl.contains(r#"<soap:address location="http://localhost:8080/quotes" />"#));
+        assert!(wsdl.contains(r#"location="http://localhost:8080/quotes?xsd=common.wsdl""#));
+        assert!(
+            wsdl.contains(r#"schemaLocation="http://localhost:8080/quotes?xsd=schemas/types.xsd""#)
+        );
+        assert!(wsdl.contains(r#"schemaLocation="http://example.com/remote.xsd""#));
+        // still a valid document, with the prefixes kept
+        Element::parse(wsdl.as_bytes()).unwrap();
+        assert!(wsdl.contains("<xsd:schema"));
+
+        let types = documents
+            .import("schemas/types.xsd", endpoint)
+            .unwrap()
+            .unwrap();
+        assert!(types.contains(
+            r#"<xsd:include schemaLocation="http://localhost:8080/quotes?xsd=shared/base.xsd" />"#
+        ));
+        assert!(documents.import("unknown.xsd", endpoint).is_none());
+    }
+
+    #[test]
+    fn load_imports() {
+        let directory =
+            std::env::temp_dir().join(format!("savon-wsdl-{}", crate::mime::random_id()));
+        std::fs::create_dir_all(directory.join("schemas")).unwrap();
+        std::fs::create_dir_all(directory.join("shared")).unwrap();
+        std::fs::write(directory.join("quotes.wsdl"), WSDL).unwrap();
+        std::fs::write(directory.join("common.wsdl"), "<definitions/>").unwrap();
+        std::fs::write(directory.join("schemas/types.xsd"), TYPES).unwrap();
+        std::fs::write(directory.join("shared/base.xsd"), "<schema/>").unwrap();
+
+        let documents = Documents::load(directory.join("quotes.wsdl")).unwrap();
+        let mut imports = documents.imports().collect::<Vec<_>>();
+        imports.sort_unstable();
+        assert_eq!(
+            imports,
+            vec!["common.wsdl", "schemas/types.xsd", "shared/base.xsd"]
+        );
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,570 @@
+//! Local SOAP server for testing generated clients.
+//!
+//! `MockServer` listens on a random local port. Responses are registered per
+//! operation name or `SOAPAction`, and every request received is recorded:
+//!
+//! ```rust,ignore
+//! let server = MockServer::start();
+//! server.on_operation(
+//!     "GetLastTradePrice",
+//!     MockResponse::fault("soap:Server.Busy", "try again later"),
+//! );
+//! server.respond_from_wsdl(&wsdl);
+//!
+//! let client = soap::StockQuoteService::new(server.url().to_string());
+//! // first call gets the fault, the next ones a default response
+//!
+//! assert_eq!(server.received()[0].operation.as_deref(), Some("GetLastTradePrice"));
+//! ```
+//!
+//! When several responses are registered for the same operation, they are
+//! returned in order, and the last one is repeated.
+//!
+//! The `cassette` module records real exchanges, to replay them later.
+
+pub mod cassette;
+
+use crate::rpser::xml::BuildElement;
+use crate::rpser::Response;
+use crate::wsdl::{SimpleType, Type, Wsdl};
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use xmltree::Element;
+
+const SOAP_NS: &str = "http://schemas.xmlsoap.org/soap/envelope/";
+const XSI_NS: &str = "http://www.w3.org/2001/XMLSchema-instance";
+const XSD_NS: &str = "http://www.w3.org/2001/XMLSchema";
+
+/// Wrap the content of a `soap:Body` in an envelope.
+pub fn envelope(body: Element) -> Element {
+    let mut envelope = Element::node("soap:Envelope")
+        .with_attr("xmlns:soap", SOAP_NS)
+        .with_attr("xmlns:xsi", XSI_NS)
+        .with_attr("xmlns:xsd", XSD_NS)
+        .with_child(Element::node("soap:Body").with_child(body));
+    crate::mtom::strip_markers(&mut envelope);
+    envelope
+}
+
+/// Response returned by the `MockServer`.
+#[derive(Clone, Debug)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl MockResponse {
+    /// Response with a `text/xml` content type.
+    pub fn new<B: Into<Vec<u8>>>(status: u16, body: B) -> Self {
+        MockResponse {
+            status,
+            headers: vec![("Content-Type".to_string(), "text/xml".to_string())],
+            body: body.into(),
+        }
+    }
+
+    /// `200` response with the given element in the `soap:Body`.
+    pub fn envelope(body: Element) -> Self {
+        MockResponse::new(200, envelope(body).to_string())
+    }
+
+    /// `500` response with a fault.
+    pub fn fault(fault_code: &str, fault_string: &str) -> Self {
+        let fault = Element::node("soap:Fault")
+            .with_child(Element::node("faultcode").with_text(fault_code))
+            .with_child(Element::node("faultstring").with_text(fault_string));
+        MockResponse::new(500, envelope(fault).to_string())
+    }
+
+    /// Replace the header with the same name, if any.
+    pub fn with_header<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        let key = key.into();
+        self.headers.retain(|(k, _)| !k.eq_ignore_ascii_case(&key));
+        self.headers.push((key, value.into()));
+        self
+    }
+}
+
+/// Request received by the `MockServer`.
+#[derive(Clone, Debug)]
+pub struct Received {
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// `SOAPAction` header, without the quotes.
+    pub soap_action: Option<String>,
+    /// Local name of the first element of the `soap:Body`.
+    pub operation: Option<String>,
+}
+
+impl Received {
+    /// Get the first header value matching `name` (case insensitive).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Parse the received envelope.
+    pub fn envelope(&self) -> Result<Response, crate::Error> {
+        Ok(Response::from_xml(&String::from_utf8_lossy(&self.body))?)
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    by_soap_action: HashMap<String, VecDeque<MockResponse>>,
+    by_operation: HashMap<String, VecDeque<MockResponse>>,
+    defaults: HashMap<String, MockResponse>,
+    received: Vec<Received>,
+}
+
+fn next(queue: Option<&mut VecDeque<MockResponse>>) -> Option<MockResponse> {
+    let queue = queue?;
+    if queue.len() > 1 {
+        queue.pop_front()
+    } else {
+        queue.front().cloned()
+    }
+}
+
+impl State {
+    fn respond(&mut self, request: Received) -> MockResponse {
+        let response = request
+            .soap_action
+            .as_ref()
+            .and_then(|action| next(self.by_soap_action.get_mut(action)))
+            .or_else(|| {
+                let operation = request.operation.as_ref()?;
+                next(self.by_operation.get_mut(operation))
+                    .or_else(|| self.defaults.get(operation).cloned())
+            })
+            .or_else(|| {
+                let action = request.soap_action.as_ref()?;
+                self.defaults.get(action).cloned()
+            })
+            .unwrap_or_else(|| {
+                MockResponse::fault(
+                    "soap:Client",
+                    &format!(
+                        "no response registered for operation {:?}, SOAPAction {:?}",
+                        request.operation, request.soap_action
+                    ),
+                )
+            });
+
+        self.received.push(request);
+        response
+    }
+}
+
+/// HTTP server answering SOAP requests with canned responses.
+///
+/// The server is stopped when dropped.
+pub struct MockServer {
+    address: SocketAddr,
+    url: String,
+    state: Arc<Mutex<State>>,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockServer {
+    /// Start a server on a random local port.
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("cannot bind the mock server");
+        let address = listener.local_addr().unwrap();
+        let state = Arc::new(Mutex::new(State::default()));
+        let running = Arc::new(AtomicBool::new(true));
+
+        let thread = {
+            let state = state.clone();
+            let running = running.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if !running.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    if let Ok(stream) = stream {
+                        if let Err(e) = serve(stream, &state) {
+                            warn!("mock server: {}", e);
+                        }
+                    }
+                }
+            })
+        };
+
+        MockServer {
+            address,
+            url: format!("http://{}/", address),
+            state,
+            running,
+            thread: Some(thread),
+        }
+    }
+
+    /// Base URL of the server, to pass to the generated client.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Answer requests for this operation, identified by the name of the
+    /// element in the `soap:Body`.
+    pub fn on_operation<S: Into<String>>(&self, operation: S, response: MockResponse) {
+        self.state
+            .lock()
+            .unwrap()
+            .by_operation
+            .entry(operation.into())
+            .or_default()
+            .push_back(response);
+    }
+
+    /// Answer requests with this `SOAPAction`. Takes precedence over
+    /// `on_operation`.
+    pub fn on_soap_action<S: Into<String>>(&self, soap_action: S, response: MockResponse) {
+        self.state
+            .lock()
+            .unwrap()
+            .by_soap_action
+            .entry(soap_action.into())
+            .or_default()
+            .push_back(response);
+    }
+
+    /// Answer the operations of the WSDL without a registered response with
+    /// a default output message, built by `default_output`.
+    pub fn respond_from_wsdl(&self, wsdl: &Wsdl) {
+        let mut state = self.state.lock().unwrap();
+
+        for (name, operation) in wsdl.operations.iter() {
+            let output = match default_output(wsdl, name) {
+                Some(output) => MockResponse::envelope(output),
+                None => continue,
+            };
+
+            if let Some(action) = operation.soap_action.as_ref() {
+                state.defaults.insert(action.clone(), output.clone());
+            }
+            state.defaults.insert(name.clone(), output);
+        }
+    }
+
+    /// Requests received so far.
+    pub fn received(&self) -> Vec<Received> {
+        self.state.lock().unwrap().received.clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        // wake up the accept loop
+        let _ = TcpStream::connect(self.address);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Read one request from the connection and write the response.
+fn serve(stream: TcpStream, state: &Mutex<State>) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let path = request_line
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or("/")
+        .to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            headers.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let content_length = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("Content-Length"))
+        .and_then(|(_, v)| v.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    let soap_action = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("SOAPAction"))
+        .map(|(_, v)| v.trim_matches('"').to_string())
+        .filter(|action| !action.is_empty());
+    let operation = Response::from_xml(&String::from_utf8_lossy(&body))
+        .ok()
+        .map(|response| response.body.name);
+
+    let response = state.lock().unwrap().respond(Received {
+        path,
+        headers,
+        body,
+        soap_action,
+        operation,
+    });
+
+    let mut stream = stream;
+    write!(stream, "HTTP/1.1 {} Mock\r\n", response.status)?;
+    for (key, value) in response.headers.iter() {
+        write!(stream, "{}: {}\r\n", key, value)?;
+    }
+    write!(
+        stream,
+        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+        response.body.len()
+    )?;
+    stream.write_all(&response.body)?;
+    stream.flush()
+}
+
+/// Build a valid output message for an operation, with default values for
+/// every required field.
+pub fn default_output(wsdl: &Wsdl, operation: &str) -> Option<Element> {
+    let output = wsdl.operations.get(operation)?.output.as_ref()?;
+    let message = wsdl.messages.get(output)?;
+    let name = format!("tns:{}", message.part_element);
+    let body = default_element(wsdl, &name, &message.part_element, &mut vec![])?;
+
+    Some(body.with_attr("xmlns:tns", wsdl.target_namespace.as_str()))
+}
+
+fn default_element(
+    wsdl: &Wsdl,
+    name: &str,
+    type_name: &str,
+    visiting: &mut Vec<String>,
+) -> Option<Element> {
+    let complex = match wsdl.types.get(type_name)? {
+        Type::Complex(complex) => complex,
+        Type::Simple(_) => return None,
+    };
+    // recursive types are only expanded once
+    if visiting.iter().any(|t| t == type_name) {
+        return None;
+    }
+    visiting.push(type_name.to_string());
+
+    let mut element = Element::node(name);
+    for (field_name, (attributes, field_type)) in complex.fields.iter() {
+        if attributes.nillable || attributes.is_optional() || attributes.is_repeated() {
+            continue;
+        }
+
+        let node = Element::node(field_name.as_str());
+        let child = match field_type {
+            SimpleType::Boolean => node.with_text("false"),
+            SimpleType::String => node.with_text("?"),
+            SimpleType::Float => node.with_text("0"),
+            SimpleType::Int => node.with_text("0"),
+            SimpleType::DateTime => node.with_text("1970-01-01T00:00:00Z"),
+            SimpleType::Base64Binary => node,
+            SimpleType::Complex(complex) => {
+                default_element(wsdl, field_name, complex, visiting).unwrap_or(node)
+            }
+        };
+        element = element.with_child(child);
+    }
+
+    visiting.pop();
+    Some(element)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gen::{FromElement, ToElements};
+    use crate::http::{request_response, Call, CallOptions};
+    use crate::interceptor::Chain;
+    use crate::retry::RetryPolicy;
+    use crate::rpser::RpcError;
+    use crate::transport::ReqwestTransport;
+    use std::time::Duration;
+
+    #[derive(Debug)]
+    struct Price(f64);
+
+    impl ToElements for Price {
+        fn to_elements(&self) -> Vec<Element> {
+            vec![Element::node("tickerSymbol").with_text("ACME")]
+        }
+    }
+
+    impl FromElement for Price {
+        fn from_element(element: &Element) -> Result<Self, crate::Error> {
+            let price = element.get_at_path(&["price"])?;
+            Ok(Price(price.get_text().unwrap_or_default().parse()?))
+        }
+    }
+
+    async fn get_price(server: &MockServer) -> Result<Result<Price, ()>, crate::Error> {
+        get_price_with_retry(server, None).await
+    }
+
+    async fn get_price_with_retry(
+        server: &MockServer,
+        retry: Option<&RetryPolicy>,
+    ) -> Result<Result<Price, ()>, crate::Error> {
+        let interceptors = Chain::new();
+        let call = Call {
+            base_url: server.url(),
+            namespace: "http://example.com/stockquote.wsdl",
+            port_type: "StockQuotePortType",
+            operation: "GetLastTradePrice",
+            input: "GetLastTradePriceRequest",
+            soap_action: Some("http://example.com/GetLastTradePrice"),
+            mtom: false,
+            retry,
+            interceptors: &interceptors,
+            options: &CallOptions::default(),
+        };
+        request_response(&ReqwestTransport::default(), &call, &Price(0.0)).await
+    }
+
+    fn price(value: &str) -> MockResponse {
+        MockResponse::envelope(
+            Element::node("TradePrice").with_child(Element::node("price").with_text(value)),
+        )
+    }
+
+    #[tokio::test]
+    async fn canned_responses() {
+        let server = MockServer::start();
+        server.on_operation("GetLastTradePrice", price("1.5"));
+        server.on_operation("GetLastTradePrice", price("2.5"));
+        server.on_operation("Other", price("3.5"));
+
+        assert_eq!(get_price(&server).await.unwrap().unwrap().0, 1.5);
+        assert_eq!(get_price(&server).await.unwrap().unwrap().0, 2.5);
+        assert_eq!(get_price(&server).await.unwrap().unwrap().0, 2.5);
+
+        server.on_soap_action(
+            "http://example.com/GetLastTradePrice",
+            MockResponse::fault("soap:Server.Busy", "busy"),
+        );
+        match get_price(&server).await {
+            Err(crate::Error::Rpc(RpcError::Fault { fault_code, .. })) => {
+                assert_eq!(fault_code, "soap:Server.Busy")
+            }
+            other => panic!("expected a fault, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn records_requests() {
+        let server = MockServer::start();
+        match get_price(&server).await {
+            Err(crate::Error::Rpc(RpcError::Fault { fault_code, .. })) => {
+                assert_eq!(fault_code, "soap:Client")
+            }
+            other => panic!("expected a fault, got {:?}", other),
+        }
+
+        let received = server.received();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].path, "/");
+        assert_eq!(received[0].header("Content-Type"), Some("text/xml"));
+        assert_eq!(
+            received[0].soap_action.as_deref(),
+            Some("http://example.com/GetLastTradePrice")
+        );
+        assert_eq!(received[0].operation.as_deref(), Some("GetLastTradePrice"));
+
+        let envelope = received[0].envelope().unwrap();
+        assert_eq!(
+            envelope.body.get_child("tickerSymbol").unwrap().get_text(),
+            Some("ACME".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn responses_from_wsdl() {
+        let wsdl = crate::wsdl::parse(include_bytes!("../assets/example.wsdl")).unwrap();
+        let output = default_output(&wsdl, "GetLastTradePrice").unwrap();
+        assert_eq!(output.name, "tns:TradePrice");
+        assert_eq!(
+            output.get_child("price").unwrap().get_text(),
+            Some("0".into())
+        );
+        assert!(default_output(&wsdl, "Unknown").is_none());
+
+        let server = MockServer::start();
+        server.respond_from_wsdl(&wsdl);
+        assert_eq!(get_price(&server).await.unwrap().unwrap().0, 0.0);
+    }
+
+    #[test]
+    fn strict_default_output() {
+        let mut wsdl = crate::wsdl::parse(include_bytes!("../assets/example.wsdl")).unwrap();
+        if let Some(Type::Complex(c)) = wsdl.types.get_mut("TradePrice") {
+            for (name, field_type) in [("volume", SimpleType::Int), ("open", SimpleType::Boolean)] {
+                c.fields
+                    .push((name.to_string(), (Default::default(), field_type)));
+            }
+        }
+
+        // generated decoders with strict types check `xsi:type` against the
+        // schema type
+        let output = default_output(&wsdl, "GetLastTradePrice").unwrap();
+        let output = Element::parse(output.to_string().as_bytes()).unwrap();
+        for (name, xsd_type) in [("price", "float"), ("volume", "int"), ("open", "boolean")] {
+            output
+                .get_child(name)
+                .unwrap()
+                .expect_type(xsd_type)
+                .unwrap();
+        }
+        assert_eq!(output.get_child("volume").unwrap().as_int(), Ok(0));
+        assert_eq!(output.get_child("open").unwrap().as_boolean(), Ok(false));
+    }
+
+    #[tokio::test]
+    async fn retried_calls() {
+        let policy = RetryPolicy::new(3)
+            .with_backoff(Duration::from_millis(1), Duration::from_millis(5))
+            .with_status(503)
+            .with_fault_code("Server.Busy");
+
+        let server = MockServer::start();
+        server.on_operation("GetLastTradePrice", MockResponse::new(503, ""));
+        server.on_operation(
+            "GetLastTradePrice",
+            MockResponse::fault("soap:Server.Busy", "busy"),
+        );
+        server.on_operation("GetLastTradePrice", price("1.5"));
+        let res = get_price_with_retry(&server, Some(&policy)).await;
+        assert_eq!(res.unwrap().unwrap().0, 1.5);
+        assert_eq!(server.received().len(), 3);
+
+        let server = MockServer::start();
+        server.on_operation("GetLastTradePrice", MockResponse::new(503, ""));
+        match get_price_with_retry(&server, Some(&policy)).await {
+            Err(crate::Error::UnexpectedResponse { status: 503, .. }) => {}
+            other => panic!("expected a 503 error, got {:?}", other),
+        }
+        assert_eq!(server.received().len(), 3);
+    }
+}
@@ -0,0 +1,843 @@
+//! Record and replay of SOAP exchanges.
+//!
+//! A `Cassette` is a `Transport` recording the requests sent and the
+//! responses received to a file, to replay them later without network
+//! access:
+//!
+//! ```rust,ignore
+//! // once, against the staging environment
+//! let transport = Cassette::record("tests/cassettes/quote.xml", ReqwestTransport::default());
+//! let client = soap::StockQuoteService::with_transport(staging_url, transport);
+//!
+//! // in CI
+//! let transport = Cassette::replay("tests/cassettes/quote.xml")?;
+//! let client = soap::StockQuoteService::with_transport(staging_url, transport);
+//! ```
+//!
+//! Requests are matched with the recorded ones by operation and by the
+//! canonical form of the `soap:Body`, see `Matcher`. When several recorded
+//! requests match, they are replayed in order, and the last one is
+//! repeated.
+//!
+//! Cassettes are meant to be committed, so credentials are removed before
+//! an exchange is written, see `Redaction`: by default the `Authorization`,
+//! `Proxy-Authorization`, `Cookie` and `Set-Cookie` HTTP headers, and the
+//! content of the `wsse:Security` SOAP header, which holds `UsernameToken`
+//! passwords and signatures. Other secrets can be removed with
+//! `Redaction::with_hook`.
+
+use crate::mime;
+use crate::mtom;
+use crate::rpser::xml::c14n::{self, Algorithm};
+use crate::rpser::xml::BuildElement;
+use crate::transport::{HttpRequest, HttpResponse, Transport};
+use async_trait::async_trait;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use xmltree::{Element, EmitterConfig, XMLNode};
+
+/// Cassette error.
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    /// The cassette file is not valid XML.
+    Parse(xmltree::ParseError),
+    /// The cassette file does not have the expected structure.
+    Format(&'static str),
+    Write(xmltree::Error),
+    Canonicalization(c14n::Error),
+    /// No recorded request matches, in `Mode::Replay`.
+    Unmatched {
+        operation: Option<String>,
+        soap_action: Option<String>,
+    },
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<xmltree::ParseError> for Error {
+    fn from(e: xmltree::ParseError) -> Self {
+        Error::Parse(e)
+    }
+}
+
+impl From<xmltree::Error> for Error {
+    fn from(e: xmltree::Error) -> Self {
+        Error::Write(e)
+    }
+}
+
+impl From<c14n::Error> for Error {
+    fn from(e: c14n::Error) -> Self {
+        Error::Canonicalization(e)
+    }
+}
+
+/// Recorded request and response.
+#[derive(Clone, Debug)]
+pub struct Interaction {
+    pub request: HttpRequest,
+    pub response: HttpResponse,
+}
+
+/// What the cassette does with requests.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// Send every request, and record it.
+    Record,
+    /// Replay recorded responses, failing on unmatched requests.
+    Replay,
+    /// Replay recorded responses, sending and recording unmatched requests.
+    ReplayOrRecord,
+}
+
+/// Replacement of the redacted values.
+pub const REDACTED: &str = "REDACTED";
+
+/// Function redacting an exchange, see `Redaction::with_hook`.
+pub type RedactionHook = Arc<dyn Fn(&mut Interaction) + Send + Sync>;
+
+/// Parts of the exchanges removed before they are recorded.
+#[derive(Clone)]
+pub struct Redaction {
+    /// HTTP headers, compared case-insensitively, whose value is replaced,
+    /// in requests and responses.
+    pub headers: Vec<String>,
+    /// Local names of the SOAP header elements whose content is replaced,
+    /// in requests and responses.
+    pub header_elements: Vec<String>,
+    hook: Option<RedactionHook>,
+}
+
+impl Default for Redaction {
+    /// Redact the authentication headers, cookies, and `wsse:Security`.
+    fn default() -> Self {
+        Redaction {
+            headers: vec![
+                "Authorization".to_string(),
+                "Proxy-Authorization".to_string(),
+                "Cookie".to_string(),
+                "Set-Cookie".to_string(),
+            ],
+            header_elements: vec!["Security".to_string()],
+            hook: None,
+        }
+    }
+}
+
+impl Redaction {
+    /// Record the exchanges verbatim.
+    pub fn none() -> Self {
+        Redaction {
+            headers: vec![],
+            header_elements: vec![],
+            hook: None,
+        }
+    }
+
+    pub fn with_header<S: Into<String>>(mut self, name: S) -> Self {
+        self.headers.push(name.into());
+        self
+    }
+
+    pub fn with_header_element<S: Into<String>>(mut self, local_name: S) -> Self {
+        self.header_elements.push(local_name.into());
+        self
+    }
+
+    /// Call `hook` on each new exchange, after the other redactions and
+    /// before it is recorded. Changing the matched parts of the request
+    /// prevents it from being replayed.
+    pub fn with_hook<F: Fn(&mut Interaction) + Send + Sync + 'static>(mut self, hook: F) -> Self {
+        self.hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Remove the credentials from an exchange.
+    pub fn apply(&self, interaction: &mut Interaction) {
+        let request = &mut interaction.request;
+        redact_headers(&mut request.headers, &self.headers);
+        if let Some(body) = self.redact_envelope(&request.headers, &request.body) {
+            // MTOM requests are recorded with the parts inlined
+            request
+                .headers
+                .retain(|(k, _)| !k.eq_ignore_ascii_case("Content-Type"));
+            request.headers.push((
+                "Content-Type".to_string(),
+                "text/xml; charset=utf-8".to_string(),
+            ));
+            request.body = body;
+        }
+
+        let response = &mut interaction.response;
+        redact_headers(&mut response.headers, &self.headers);
+        if let Some(body) = self.redact_envelope(&response.headers, &response.body) {
+            response
+                .headers
+                .retain(|(k, _)| !k.eq_ignore_ascii_case("Content-Type"));
+            response.headers.push((
+                "Content-Type".to_string(),
+                "text/xml; charset=utf-8".to_string(),
+            ));
+            response.body = body;
+        }
+
+        if let Some(hook) = self.hook.as_ref() {
+            hook(interaction);
+        }
+    }
+
+    /// The envelope without the content of the redacted header elements,
+    /// or `None` if it does not have any.
+    fn redact_envelope(&self, headers: &[(String, String)], body: &[u8]) -> Option<Vec<u8>> {
+        if self.header_elements.is_empty() {
+            return None;
+        }
+        let mut envelope = parse_envelope(headers, body)?;
+        let header = envelope.get_mut_child("Header")?;
+
+        let mut redacted = false;
+        for child in header.children.iter_mut() {
+            if let XMLNode::Element(e) = child {
+                if self.header_elements.contains(&e.name) {
+                    e.children = vec![XMLNode::Text(REDACTED.to_string())];
+                    redacted = true;
+                }
+            }
+        }
+        if !redacted {
+            return None;
+        }
+
+        let mut xml = Vec::new();
+        envelope.write(&mut xml).ok()?;
+        Some(xml)
+    }
+}
+
+fn redact_headers(headers: &mut [(String, String)], redacted: &[String]) {
+    for (name, value) in headers.iter_mut() {
+        if redacted.iter().any(|r| r.eq_ignore_ascii_case(name)) {
+            *value = REDACTED.to_string();
+        }
+    }
+}
+
+/// Parts of the request compared with the recorded ones.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Key {
+    pub operation: Option<String>,
+    pub soap_action: Option<String>,
+    pub url: Option<String>,
+    /// Canonical `soap:Body`.
+    pub body: Option<String>,
+}
+
+/// How requests are matched with the recorded ones.
+#[derive(Clone, Debug)]
+pub struct Matcher {
+    pub operation: bool,
+    pub soap_action: bool,
+    pub url: bool,
+    pub body: bool,
+    /// Local names of the elements removed from the body before comparing,
+    /// like generated identifiers.
+    pub ignored_elements: Vec<String>,
+}
+
+impl Default for Matcher {
+    /// Match on the operation and the body.
+    fn default() -> Self {
+        Matcher {
+            operation: true,
+            soap_action: false,
+            url: false,
+            body: true,
+            ignored_elements: vec![],
+        }
+    }
+}
+
+impl Matcher {
+    pub fn with_soap_action(mut self, enabled: bool) -> Self {
+        self.soap_action = enabled;
+        self
+    }
+
+    pub fn with_url(mut self, enabled: bool) -> Self {
+        self.url = enabled;
+        self
+    }
+
+    pub fn with_body(mut self, enabled: bool) -> Self {
+        self.body = enabled;
+        self
+    }
+
+    /// Ignore the elements with this local name when comparing bodies.
+    pub fn ignoring<S: Into<String>>(mut self, local_name: S) -> Self {
+        self.ignored_elements.push(local_name.into());
+        self
+    }
+
+    /// Compute the parts of the request to compare.
+    pub fn key(&self, request: &HttpRequest) -> Result<Key, Error> {
+        let body = envelope_body(request)?;
+
+        let operation = body.as_ref().and_then(|body| {
+            body.children
+                .iter()
+                .filter_map(|c| c.as_element())
+                .next()
+                .map(|e| e.name.clone())
+        });
+        let canonical = match body {
+            Some(mut body) if self.body => {
+                strip(&mut body, &self.ignored_elements);
+                Some(c14n::element(
+                    &body,
+                    Algorithm::Exclusive {
+                        inclusive_prefixes: &[],
+                    },
+                    false,
+                )?)
+            }
+            _ => None,
+        };
+
+        Ok(Key {
+            operation: operation.filter(|_| self.operation),
+            soap_action: soap_action(request).filter(|_| self.soap_action),
+            url: Some(request.url.clone()).filter(|_| self.url),
+            body: canonical,
+        })
+    }
+}
+
+fn soap_action(request: &HttpRequest) -> Option<String> {
+    request
+        .header("SOAPAction")
+        .map(|action| action.trim_matches('"').to_string())
+        .filter(|action| !action.is_empty())
+}
+
+/// Parse the `soap:Body` of a request.
+fn envelope_body(request: &HttpRequest) -> Result<Option<Element>, Error> {
+    // not an envelope, only the other parts of the request are compared
+    Ok(parse_envelope(&request.headers, &request.body)
+        .and_then(|envelope| envelope.get_child("Body").cloned()))
+}
+
+/// Parse a SOAP envelope, decoding MTOM messages.
+fn parse_envelope(headers: &[(String, String)], body: &[u8]) -> Option<Element> {
+    let decoded;
+    let mut xml = body;
+    if let Some(content_type) = crate::transport::find_header(headers, "Content-Type") {
+        if mime::media_type(content_type) == "multipart/related" {
+            if let Ok((envelope, _)) = mtom::decode_inline(content_type, xml) {
+                decoded = envelope;
+                xml = decoded.as_slice();
+            }
+        }
+    }
+    Element::parse(xml).ok()
+}
+
+/// Remove the ignored elements and the whitespace between elements.
+fn strip(element: &mut Element, ignored: &[String]) {
+    element.children.retain(|child| match child {
+        XMLNode::Element(e) => !ignored.contains(&e.name),
+        XMLNode::Text(text) => !text.trim().is_empty(),
+        _ => true,
+    });
+    for child in element.children.iter_mut() {
+        if let XMLNode::Element(child) = child {
+            strip(child, ignored);
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    interactions: Vec<Interaction>,
+    /// Keys of the recorded requests, `None` if they cannot be computed.
+    keys: Vec<Option<Key>>,
+    replayed: Vec<bool>,
+}
+
+impl State {
+    fn new(interactions: Vec<Interaction>, matcher: &Matcher) -> Self {
+        State {
+            keys: interactions
+                .iter()
+                .map(|i| matcher.key(&i.request).ok())
+                .collect(),
+            replayed: vec![false; interactions.len()],
+            interactions,
+        }
+    }
+}
+
+/// Recording and replaying `Transport`.
+pub struct Cassette {
+    path: PathBuf,
+    mode: Mode,
+    matcher: Matcher,
+    redaction: Redaction,
+    transport: Option<Box<dyn Transport>>,
+    state: Mutex<State>,
+}
+
+impl Cassette {
+    /// Record all the exchanges to `path`, replacing its content.
+    pub fn record<P: AsRef<Path>, T: Transport + 'static>(path: P, transport: T) -> Self {
+        Cassette {
+            path: path.as_ref().to_path_buf(),
+            mode: Mode::Record,
+            matcher: Matcher::default(),
+            redaction: Redaction::default(),
+            transport: Some(Box::new(transport)),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Replay the exchanges recorded in `path`, failing on unmatched
+    /// requests.
+    pub fn replay<P: AsRef<Path>>(path: P) -> Result<Self, crate::Error> {
+        let path = path.as_ref().to_path_buf();
+        let interactions = load(&path)?;
+
+        Ok(Cassette {
+            path,
+            mode: Mode::Replay,
+            matcher: Matcher::default(),
+            redaction: Redaction::default(),
+            transport: None,
+            state: Mutex::new(State::new(interactions, &Matcher::default())),
+        })
+    }
+
+    /// Replay the exchanges recorded in `path` if it exists, and record the
+    /// unmatched requests.
+    pub fn replay_or_record<P: AsRef<Path>, T: Transport + 'static>(
+        path: P,
+        transport: T,
+    ) -> Result<Self, crate::Error> {
+        let path = path.as_ref().to_path_buf();
+        let interactions = if path.exists() { load(&path)? } else { vec![] };
+
+        Ok(Cassette {
+            path,
+            mode: Mode::ReplayOrRecord,
+            matcher: Matcher::default(),
+            redaction: Redaction::default(),
+            transport: Some(Box::new(transport)),
+            state: Mutex::new(State::new(interactions, &Matcher::default())),
+        })
+    }
+
+    pub fn with_matcher(mut self, matcher: Matcher) -> Self {
+        let state = self.state.get_mut().unwrap();
+        *state = State::new(std::mem::take(&mut state.interactions), &matcher);
+        self.matcher = matcher;
+        self
+    }
+
+    /// Parts of the new exchanges removed before they are recorded,
+    /// `Redaction::default()` if not set.
+    pub fn with_redaction(mut self, redaction: Redaction) -> Self {
+        self.redaction = redaction;
+        self
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Recorded exchanges, including the ones loaded from the file.
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.state.lock().unwrap().interactions.clone()
+    }
+
+    /// Write the recorded exchanges to the file. This is done after each
+    /// new exchange.
+    pub fn save(&self) -> Result<(), crate::Error> {
+        let state = self.state.lock().unwrap();
+        save(&self.path, &state.interactions)?;
+        Ok(())
+    }
+
+    fn find(&self, request: &HttpRequest) -> Result<Option<HttpResponse>, Error> {
+        let key = self.matcher.key(request)?;
+        let mut state = self.state.lock().unwrap();
+
+        let matching = state
+            .keys
+            .iter()
+            .enumerate()
+            .filter(|(_, k)| k.as_ref() == Some(&key))
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+
+        let index = match matching.iter().find(|index| !state.replayed[**index]) {
+            Some(index) => *index,
+            None => match matching.last() {
+                Some(index) => *index,
+                None => return Ok(None),
+            },
+        };
+        state.replayed[index] = true;
+        Ok(Some(state.interactions[index].response.clone()))
+    }
+}
+
+#[async_trait]
+impl Transport for Cassette {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, crate::Error> {
+        if self.mode != Mode::Record {
+            if let Some(response) = self.find(&request)? {
+                return Ok(response);
+            }
+        }
+
+        let transport = match (self.mode, self.transport.as_ref()) {
+            (Mode::Replay, _) | (_, None) => {
+                let key = Matcher::default().key(&request)?;
+                return Err(Error::Unmatched {
+                    operation: key.operation,
+                    soap_action: soap_action(&request),
+                }
+                .into());
+            }
+            (_, Some(transport)) => transport,
+        };
+
+        let response = transport.send(request.clone()).await?;
+
+        let mut interaction = Interaction {
+            request,
+            response: response.clone(),
+        };
+        self.redaction.apply(&mut interaction);
+        // computed on the recorded request, as when the cassette is loaded
+        let key = self.matcher.key(&interaction.request).ok();
+
+        let mut state = self.state.lock().unwrap();
+        state.interactions.push(interaction);
+        state.keys.push(key);
+        state.replayed.push(true);
+        save(&self.path, &state.interactions)?;
+
+        Ok(response)
+    }
+}
+
+fn headers_to_elements(headers: &[(String, String)]) -> Vec<Element> {
+    headers
+        .iter()
+        .map(|(k, v)| {
+            Element::node("header")
+                .with_attr("name", k.as_str())
+                .with_attr("value", v.as_str())
+        })
+        .collect()
+}
+
+fn headers_from_element(element: &Element) -> Vec<(String, String)> {
+    element
+        .children
+        .iter()
+        .filter_map(|c| c.as_element())
+        .filter(|c| c.name == "header")
+        .filter_map(|c| {
+            Some((
+                c.attributes.get("name")?.clone(),
+                c.attributes.get("value")?.clone(),
+            ))
+        })
+        .collect()
+}
+
+fn body_from_element(element: &Element) -> Result<Vec<u8>, Error> {
+    let body = element
+        .get_child("body")
+        .ok_or(Error::Format("missing body"))?;
+    body.as_binary().map_err(|_| Error::Format("invalid body"))
+}
+
+fn save(path: &Path, interactions: &[Interaction]) -> Result<(), Error> {
+    let cassette = Element::node("cassette").with_children(interactions.iter().map(|i| {
+        Element::node("interaction")
+            .with_child(
+                Element::node("request")
+                    .with_attr("url", i.request.url.as_str())
+                    .with_children(headers_to_elements(&i.request.headers))
+                    .with_child(Element::node("body").with_text(base64::encode(&i.request.body))),
+            )
+            .with_child(
+                Element::node("response")
+                    .with_attr("status", i.response.status.to_string())
+                    .with_children(headers_to_elements(&i.response.headers))
+                    .with_child(Element::node("body").with_text(base64::encode(&i.response.body))),
+            )
+    }));
+
+    let file = File::create(path)?;
+    cassette.write_with_config(file, EmitterConfig::new().perform_indent(true))?;
+    Ok(())
+}
+
+fn load(path: &Path) -> Result<Vec<Interaction>, Error> {
+    let cassette = Element::parse(File::open(path)?)?;
+
+    cassette
+        .children
+        .iter()
+        .filter_map(|c| c.as_element())
+        .filter(|c| c.name == "interaction")
+        .map(|interaction| {
+            let request = interaction
+                .get_child("request")
+                .ok_or(Error::Format("missing request"))?;
+            let response = interaction
+                .get_child("response")
+                .ok_or(Error::Format("missing response"))?;
+
+            Ok(Interaction {
+                request: HttpRequest {
+                    url: request.attributes.get("url").cloned().unwrap_or_default(),
+                    headers: headers_from_element(request),
+                    body: body_from_element(request)?,
+                    timeout: None,
+                },
+                response: HttpResponse {
+                    status: response
+                        .attributes
+                        .get("status")
+                        .and_then(|s| s.parse().ok())
+                        .ok_or(Error::Format("invalid status"))?,
+                    headers: headers_from_element(response),
+                    body: body_from_element(response)?,
+                },
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gen::{FromElement, ToElements};
+    use crate::http::{request_response, Call, CallOptions};
+    use crate::interceptor::Chain;
+    use crate::rpser::Method;
+    use crate::testing::{MockResponse, MockServer};
+    use crate::transport::ReqwestTransport;
+
+    #[derive(Debug)]
+    struct Echo(String);
+
+    impl ToElements for Echo {
+        fn to_elements(&self) -> Vec<Element> {
+            vec![Element::node("value").with_text(self.0.clone())]
+        }
+    }
+
+    impl FromElement for Echo {
+        fn from_element(element: &Element) -> Result<Self, crate::Error> {
+            let value = element.get_at_path(&["value"])?;
+            Ok(Echo(value.get_text().unwrap_or_default().to_string()))
+        }
+    }
+
+    fn path() -> PathBuf {
+        std::env::temp_dir().join(format!("savon-cassette-{}.xml", mime::random_id()))
+    }
+
+    fn server() -> MockServer {
+        let server = MockServer::start();
+        for value in &["one", "two"] {
+            server.on_operation(
+                "Echo",
+                MockResponse::envelope(
+                    Element::node("ns:EchoResponse")
+                        .with_attr("xmlns:ns", "urn:test")
+                        .with_child(Element::node("value").with_text(*value)),
+                ),
+            );
+        }
+        server
+    }
+
+    async fn echo<T: Transport>(
+        transport: &T,
+        url: &str,
+        value: &str,
+    ) -> Result<Result<Echo, ()>, crate::Error> {
+        let interceptors = Chain::new();
+        let call = Call {
+            base_url: url,
+            namespace: "urn:test",
+            port_type: "TestPortType",
+            operation: "Echo",
+            input: "EchoRequest",
+            soap_action: None,
+            mtom: false,
+            retry: None,
+            interceptors: &interceptors,
+            options: &CallOptions::default(),
+        };
+        request_response(transport, &call, &Echo(value.to_string())).await
+    }
+
+    fn request(value: &str, message_id: &str) -> HttpRequest {
+        let envelope = Method::new("Echo")
+            .with(Element::node("MessageID").with_text(message_id))
+            .with(Element::node("value").with_text(value))
+            .as_xml("urn:test");
+        HttpRequest::new("http://localhost/echo", envelope.into_bytes())
+    }
+
+    #[tokio::test]
+    async fn record_and_replay() {
+        let path = path();
+        let server = server();
+        let url = server.url().to_string();
+
+        let cassette = Cassette::record(&path, ReqwestTransport::default());
+        assert_eq!(echo(&cassette, &url, "a").await.unwrap().unwrap().0, "one");
+        assert_eq!(echo(&cassette, &url, "a").await.unwrap().unwrap().0, "two");
+        assert_eq!(echo(&cassette, &url, "b").await.unwrap().unwrap().0, "two");
+        drop(server);
+
+        let cassette = Cassette::replay(&path).unwrap();
+        assert_eq!(cassette.interactions().len(), 3);
+        assert_eq!(echo(&cassette, &url, "b").await.unwrap().unwrap().0, "two");
+        assert_eq!(echo(&cassette, &url, "a").await.unwrap().unwrap().0, "one");
+        assert_eq!(echo(&cassette, &url, "a").await.unwrap().unwrap().0, "two");
+        assert_eq!(echo(&cassette, &url, "a").await.unwrap().unwrap().0, "two");
+
+        match echo(&cassette, &url, "c").await {
+            Err(crate::Error::Cassette(Error::Unmatched { operation, .. })) => {
+                assert_eq!(operation.as_deref(), Some("Echo"))
+            }
+            other => panic!("expected an unmatched request, got {:?}", other),
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn replay_or_record() {
+        let path = path();
+        let server = server();
+
+        let cassette = Cassette::replay_or_record(&path, ReqwestTransport::default()).unwrap();
+        assert_eq!(cassette.mode(), Mode::ReplayOrRecord);
+        assert_eq!(
+            echo(&cassette, server.url(), "a").await.unwrap().unwrap().0,
+            "one"
+        );
+        assert_eq!(
+            echo(&cassette, server.url(), "a").await.unwrap().unwrap().0,
+            "one"
+        );
+        assert_eq!(server.received().len(), 1);
+
+        let cassette = Cassette::replay_or_record(&path, ReqwestTransport::default()).unwrap();
+        assert_eq!(
+            echo(&cassette, server.url(), "a").await.unwrap().unwrap().0,
+            "one"
+        );
+        assert_eq!(
+            echo(&cassette, server.url(), "b").await.unwrap().unwrap().0,
+            "two"
+        );
+        assert_eq!(server.received().len(), 2);
+        assert_eq!(Cassette::replay(&path).unwrap().interactions().len(), 2);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn redaction() {
+        use crate::security::{Security, UsernameToken};
+
+        let path = path();
+        let server = server();
+        let mut envelope = Element::parse(request("a", "1").body.as_slice()).unwrap();
+        Security::new()
+            .with_username_token(UsernameToken::text("user", "secret-password"))
+            .apply(&mut envelope);
+        let request = HttpRequest::new(server.url(), envelope.to_string().into_bytes())
+            .with_header("authorization", "Bearer secret-token")
+            .with_header("SOAPAction", "\"urn:test/Echo\"");
+
+        let cassette = Cassette::record(&path, ReqwestTransport::default()).with_redaction(
+            Redaction::default()
+                .with_header("SOAPAction")
+                .with_hook(|i| i.request.url = "http://localhost/".to_string()),
+        );
+        cassette.send(request.clone()).await.unwrap();
+        // the file is written with the redacted exchange
+        let file = std::fs::read_to_string(&path).unwrap();
+        assert!(!file.contains("secret-token"));
+
+        let recorded = &Cassette::replay(&path).unwrap().interactions()[0];
+        let body = String::from_utf8(recorded.request.body.clone()).unwrap();
+        assert!(!body.contains("secret-password"));
+        assert!(body.contains("<wsse:Security"));
+        assert_eq!(recorded.request.header("Authorization"), Some(REDACTED));
+        assert_eq!(recorded.request.header("SOAPAction"), Some(REDACTED));
+        assert_eq!(recorded.request.url, "http://localhost/");
+
+        // the body is still matched
+        let cassette = Cassette::replay(&path).unwrap();
+        assert_eq!(cassette.send(request.clone()).await.unwrap().status, 200);
+
+        Cassette::record(&path, ReqwestTransport::default())
+            .with_redaction(Redaction::none())
+            .send(request)
+            .await
+            .unwrap();
+        let recorded = &Cassette::replay(&path).unwrap().interactions()[0];
+        assert_eq!(
+            recorded.request.header("Authorization"),
+            Some("Bearer secret-token")
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn matching() {
+        let matcher = Matcher::default();
+        let key = matcher.key(&request("a", "1")).unwrap();
+        assert_eq!(key.operation.as_deref(), Some("Echo"));
+        assert_eq!(key.url, None);
+        assert_ne!(key, matcher.key(&request("a", "2")).unwrap());
+
+        let matcher = Matcher::default().ignoring("MessageID");
+        assert_eq!(
+            matcher.key(&request("a", "1")).unwrap(),
+            matcher.key(&request("a", "2")).unwrap()
+        );
+        assert_ne!(
+            matcher.key(&request("a", "1")).unwrap(),
+            matcher.key(&request("b", "1")).unwrap()
+        );
+
+        let matcher = Matcher::default().with_body(false).with_url(true);
+        let other = HttpRequest::new("http://localhost/other", request("b", "2").body);
+        assert_eq!(
+            matcher.key(&request("a", "1")).unwrap(),
+            matcher.key(&request("b", "2")).unwrap()
+        );
+        assert_ne!(
+            matcher.key(&request("a", "1")).unwrap(),
+            matcher.key(&other).unwrap()
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! HTTP transport abstraction used by the generated clients.
+//!
+//! The runtime builds an `HttpRequest` containing the serialized envelope,
+//! hands it to a `Transport`, and parses the returned `HttpResponse`. The
+//! default implementation is based on reqwest, but any HTTP stack (or an
+//! in-process mock) can be plugged in by implementing `Transport`.
+
+use async_trait::async_trait;
+use std::time::Duration;
+
+/// HTTP request carrying a SOAP envelope.
+#[derive(Clone, Debug)]
+pub struct HttpRequest {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Timeout of the request, the transport's default if `None`.
+    pub timeout: Option<Duration>,
+}
+
+impl HttpRequest {
+    /// Create a `POST` request to `url` with the given body.
+    pub fn new<S: Into<String>>(url: S, body: Vec<u8>) -> HttpRequest {
+        HttpRequest {
+            url: url.into(),
+            headers: vec![],
+            body,
+            timeout: None,
+        }
+    }
+
+    /// Add a header.
+    pub fn with_header<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.headers.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Get the first header value matching `name` (case insensitive).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+}
+
+/// HTTP response as returned by a `Transport`.
+#[derive(Clone, Debug)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    /// Get the first header value matching `name` (case insensitive).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+}
+
+pub(crate) fn find_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// Sends SOAP requests over HTTP.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Send the request and return the raw response.
+    ///
+    /// Implementations should only fail on transport level errors: HTTP
+    /// error statuses are returned as a regular `HttpResponse`.
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, crate::Error>;
+}
+
+#[async_trait]
+impl<T: Transport + ?Sized> Transport for std::sync::Arc<T> {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, crate::Error> {
+        (**self).send(request).await
+    }
+}
+
+/// `Transport` implementation based on `reqwest::Client`.
+#[derive(Clone, Debug, Default)]
+pub struct ReqwestTransport {
+    pub client: reqwest::Client,
+}
+
+impl ReqwestTransport {
+    pub fn new(client: reqwest::Client) -> Self {
+        ReqwestTransport { client }
+    }
+}
+
+#[async_trait]
+impl Transport for ReqwestTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, crate::Error> {
+        let mut builder = self.client.post(&request.url);
+        for (key, value) in request.headers.iter() {
+            builder = builder.header(key.as_str(), value.as_str());
+        }
+        if let Some(timeout) = request.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        let response = builder.body(request.body).send().await?;
+
+        let status = response.status().as_u16();
+        let headers = response
+            .headers()
+            .iter()
+            .filter_map(|(k, v)| {
+                v.to_str()
+                    .ok()
+                    .map(|v| (k.as_str().to_string(), v.to_string()))
+            })
+            .collect();
+        let body = response.bytes().await?.to_vec();
+
+        Ok(HttpResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+/// Synchronous transports, used by the blocking clients.
+#[cfg(feature = "blocking")]
+pub mod blocking {
+    use super::{HttpRequest, HttpResponse};
+
+    /// Sends SOAP requests over HTTP, blocking the current thread.
+    pub trait Transport: Send + Sync {
+        /// Send the request and return the raw response.
+        ///
+        /// As for the async `Transport`, HTTP error statuses are returned as
+        /// a regular `HttpResponse`.
+        fn send(&self, request: HttpRequest) -> Result<HttpResponse, crate::Error>;
+    }
+
+    impl<T: Transport + ?Sized> Transport for std::sync::Arc<T> {
+        fn send(&self, request: HttpRequest) -> Result<HttpResponse, crate::Error> {
+            (**self).send(request)
+        }
+    }
+
+    /// `Transport` implementation based on `reqwest::blocking::Client`.
+    #[derive(Clone, Debug, Default)]
+    pub struct ReqwestTransport {
+        pub client: reqwest::blocking::Client,
+    }
+
+    impl ReqwestTransport {
+        pub fn new(client: reqwest::blocking::Client) -> Self {
+            ReqwestTransport { client }
+        }
+    }
+
+    impl Transport for ReqwestTransport {
+        fn send(&self, request: HttpRequest) -> Result<HttpResponse, crate::Error> {
+            let mut builder = self.client.post(&request.url);
+            for (key, value) in request.headers.iter() {
+                builder = builder.header(key.as_str(), value.as_str());
+            }
+            if let Some(timeout) = request.timeout {
+                builder = builder.timeout(timeout);
+            }
+
+            let response = builder.body(request.body).send()?;
+
+            let status = response.status().as_u16();
+            let headers = response
+                .headers()
+                .iter()
+                .filter_map(|(k, v)| {
+                    v.to_str()
+                        .ok()
+                        .map(|v| (k.as_str().to_string(), v.to_string()))
+                })
+                .collect();
+            let body = response.bytes()?.to_vec();
+
+            Ok(HttpResponse {
+                status,
+                headers,
+                body,
+            })
+        }
+    }
+}